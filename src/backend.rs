@@ -0,0 +1,398 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use log::{debug, warn};
+use serde::Deserialize;
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+use crate::{CaptureKind, format::Format, hypr, tool_version, tools::Tools};
+
+/// One capture as `take` asks for it
+pub struct Request<'a> {
+    pub kind: CaptureKind,
+    /// grimblast's action (`save`, `copy`, `copysave` or `edit`); backends
+    /// that don't [`handles_actions`](Backend::handles_actions) only get `save`
+    pub action: &'a str,
+    pub dest: &'a Path,
+    pub notify: bool,
+    pub cursor: bool,
+    /// `--output`, for backends that need an output name up front
+    pub output: Option<&'a str>,
+}
+
+/// A capture engine. `take` only deals with the png it writes; everything
+/// after that (conversion, moving, hooks) is shared
+pub trait Backend {
+    fn name(&self) -> &'static str;
+
+    /// The capture tool's version line, for image metadata, if it has one
+    fn version(&self) -> Option<String> {
+        None
+    }
+
+    /// Programs that must be installed to capture `kind`, and to copy it to
+    /// the clipboard when `copies`
+    fn required(&self, kind: CaptureKind, copies: bool) -> Vec<PathBuf>;
+
+    /// Whether the capture command copies or opens an editor itself; if not,
+    /// crabture saves and does the copying afterwards
+    fn handles_actions(&self) -> bool {
+        false
+    }
+
+    /// What `kind` really captures, e.g. the screen when no window has focus
+    fn resolve(&self, kind: CaptureKind) -> CaptureKind {
+        kind
+    }
+
+    /// Run right before the capture, after `--dry-run` has had its say; the
+    /// returned guard undoes it once the capture is done
+    fn prepare(
+        &self,
+        _kind: CaptureKind,
+        _output: Option<&str>,
+    ) -> Result<Option<hypr::RestoreFocus>> {
+        Ok(None)
+    }
+
+    fn command(&self, req: &Request) -> Result<Command>;
+
+    /// Whether a failed run just means the user dismissed the area selection
+    fn cancelled(&self, kind: CaptureKind, output: &Output) -> bool;
+
+    /// The clipboard writer, reading data of type `mime` on stdin
+    fn clipboard(&self, mime: &str) -> Command;
+
+    /// Whether hyprpicker freezing and the slurp selector shim apply
+    fn wayland(&self) -> bool;
+}
+
+/// `--backend` choices
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    /// grimblast on Wayland, maim on X11
+    #[default]
+    Auto,
+    Grimblast,
+    /// grim and slurp directly, without grimblast
+    Grim,
+    X11,
+}
+
+/// The backend for `kind`; `Auto` picks X11 when the session says so, or when
+/// there's a `DISPLAY` but no Wayland
+pub fn select(kind: Kind, tools: &Tools, grimblast_args: Vec<String>) -> Box<dyn Backend> {
+    let kind = match kind {
+        Kind::Auto if x11_session() => Kind::X11,
+        Kind::Auto => Kind::Grimblast,
+        kind => kind,
+    };
+    debug!("capturing with the {kind:?} backend");
+    if kind != Kind::Grimblast && !grimblast_args.is_empty() {
+        warn!("--grimblast-arg only applies to the grimblast backend");
+    }
+    match kind {
+        Kind::Grim => Box::new(Grim),
+        Kind::X11 => Box::new(X11),
+        _ => Box::new(Grimblast {
+            program: tools.grimblast.clone(),
+            extra_args: grimblast_args,
+        }),
+    }
+}
+
+fn x11_session() -> bool {
+    let var = |name| env::var(name).unwrap_or_default();
+    match var("XDG_SESSION_TYPE").as_str() {
+        "x11" => true,
+        "wayland" => false,
+        _ => var("WAYLAND_DISPLAY").is_empty() && !var("DISPLAY").is_empty(),
+    }
+}
+
+/// Hyprland (or any wlroots compositor) through grimblast
+pub struct Grimblast {
+    pub program: PathBuf,
+    pub extra_args: Vec<String>,
+}
+
+impl Backend for Grimblast {
+    fn name(&self) -> &'static str {
+        "grimblast"
+    }
+
+    fn version(&self) -> Option<String> {
+        tool_version(&self.program, "--version")
+    }
+
+    fn required(&self, _kind: CaptureKind, _copies: bool) -> Vec<PathBuf> {
+        // grimblast runs slurp and wl-copy itself and names what is missing
+        vec![self.program.clone()]
+    }
+
+    fn handles_actions(&self) -> bool {
+        true
+    }
+
+    fn resolve(&self, kind: CaptureKind) -> CaptureKind {
+        resolve_active(kind)
+    }
+
+    fn prepare(
+        &self,
+        kind: CaptureKind,
+        output: Option<&str>,
+    ) -> Result<Option<hypr::RestoreFocus>> {
+        let (CaptureKind::Output, Some(name)) = (kind, output) else {
+            return Ok(None);
+        };
+        let restore = hypr::RestoreFocus::save();
+        hypr::focus_monitor(name)?;
+        Ok(restore)
+    }
+
+    fn command(&self, req: &Request) -> Result<Command> {
+        let mut cmd = Command::new(&self.program);
+        if req.notify {
+            cmd.arg("--notify");
+        }
+        if req.cursor {
+            cmd.arg("--cursor");
+        }
+        cmd.args(&self.extra_args);
+        cmd.args([req.action, req.kind.as_str()]).arg(req.dest);
+        Ok(cmd)
+    }
+
+    fn cancelled(&self, kind: CaptureKind, output: &Output) -> bool {
+        slurp_cancelled(kind, output)
+    }
+
+    fn clipboard(&self, mime: &str) -> Command {
+        wl_copy(mime)
+    }
+
+    fn wayland(&self) -> bool {
+        true
+    }
+}
+
+/// grim for the pixels and slurp for the selection, with crabture doing the
+/// clipboard and editor itself
+pub struct Grim;
+
+impl Backend for Grim {
+    fn name(&self) -> &'static str {
+        "grim"
+    }
+
+    fn required(&self, kind: CaptureKind, copies: bool) -> Vec<PathBuf> {
+        let mut tools = vec!["grim".into()];
+        if kind == CaptureKind::Area {
+            tools.push("slurp".into());
+        }
+        if copies {
+            tools.push("wl-copy".into());
+        }
+        tools
+    }
+
+    fn resolve(&self, kind: CaptureKind) -> CaptureKind {
+        resolve_active(kind)
+    }
+
+    fn command(&self, req: &Request) -> Result<Command> {
+        let mut cmd;
+        match req.kind {
+            // a shell so the selection happens when the command runs, not when
+            // it is built (and not at all under --dry-run)
+            CaptureKind::Area => {
+                cmd = Command::new("sh");
+                // $0 is the file; the flags added below land in "$@"
+                cmd.args([
+                    "-c",
+                    r#"geometry=$(slurp) || exit 1; exec grim "$@" -g "$geometry" "$0""#,
+                ]);
+                cmd.arg(req.dest);
+            }
+            CaptureKind::Screen => cmd = Command::new("grim"),
+            CaptureKind::Output => {
+                let name = match req.output {
+                    Some(name) => name.to_string(),
+                    None => hypr::monitors()?
+                        .into_iter()
+                        .find(|m| m.focused)
+                        .map(|m| m.name)
+                        .context("no focused output; pass --output NAME")?,
+                };
+                cmd = Command::new("grim");
+                cmd.args(["-o", &name]);
+            }
+            CaptureKind::ActiveWindow => {
+                let win = hypr::active_window()?.context("no window is focused")?;
+                cmd = Command::new("grim");
+                cmd.args([
+                    "-g",
+                    &format!("{},{} {}x{}", win.at.0, win.at.1, win.size.0, win.size.1),
+                ]);
+            }
+        }
+        if req.cursor {
+            cmd.arg("-c");
+        }
+        if req.kind != CaptureKind::Area {
+            cmd.arg(req.dest);
+        }
+        Ok(cmd)
+    }
+
+    fn cancelled(&self, kind: CaptureKind, output: &Output) -> bool {
+        slurp_cancelled(kind, output)
+    }
+
+    fn clipboard(&self, mime: &str) -> Command {
+        wl_copy(mime)
+    }
+
+    fn wayland(&self) -> bool {
+        true
+    }
+}
+
+// hyprctl reads the same geometry grimblast's `active` target uses; checking
+// first lets us fall back instead of failing when nothing has focus
+fn resolve_active(kind: CaptureKind) -> CaptureKind {
+    if kind != CaptureKind::ActiveWindow {
+        return kind;
+    }
+    match hypr::active_window() {
+        Ok(Some(win)) => {
+            debug!(
+                "active window at {},{} size {}x{}",
+                win.at.0, win.at.1, win.size.0, win.size.1
+            );
+            kind
+        }
+        Ok(None) => {
+            warn!("no window is focused, capturing the whole screen instead");
+            CaptureKind::Screen
+        }
+        Err(e) => {
+            warn!("{e:#}, capturing the whole screen instead");
+            CaptureKind::Screen
+        }
+    }
+}
+
+/// grimblast exits 1 when slurp returns no geometry, and slurp itself only
+/// complains with "selection cancelled"; anything else is a real failure
+fn slurp_cancelled(kind: CaptureKind, output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    matches!(kind, CaptureKind::Area)
+        && output.status.code() == Some(1)
+        && (stderr.is_empty() || stderr.ends_with("selection cancelled"))
+}
+
+fn wl_copy(mime: &str) -> Command {
+    let mut cmd = Command::new("wl-copy");
+    cmd.args(["--type", mime]);
+    cmd
+}
+
+/// Plain X11 through maim (which runs slop for selections), xclip and xdotool
+pub struct X11;
+
+impl Backend for X11 {
+    fn name(&self) -> &'static str {
+        "maim"
+    }
+
+    fn version(&self) -> Option<String> {
+        tool_version(Path::new("maim"), "--version")
+    }
+
+    fn required(&self, kind: CaptureKind, copies: bool) -> Vec<PathBuf> {
+        let mut tools = vec!["maim".into()];
+        if kind == CaptureKind::ActiveWindow {
+            tools.push("xdotool".into());
+        }
+        if copies {
+            tools.push("xclip".into());
+        }
+        tools
+    }
+
+    fn resolve(&self, kind: CaptureKind) -> CaptureKind {
+        match kind {
+            CaptureKind::Output => {
+                warn!("X11 has no focused output, capturing the whole screen instead");
+                CaptureKind::Screen
+            }
+            CaptureKind::ActiveWindow if active_window_id().is_none() => {
+                warn!(
+                    "no active window (is xdotool installed?), capturing the whole screen instead"
+                );
+                CaptureKind::Screen
+            }
+            _ => kind,
+        }
+    }
+
+    fn command(&self, req: &Request) -> Result<Command> {
+        let mut cmd = Command::new("maim");
+        // maim draws the pointer unless told otherwise, unlike grimblast
+        if !req.cursor {
+            cmd.arg("--hidecursor");
+        }
+        match req.kind {
+            CaptureKind::Area => {
+                cmd.arg("--select");
+            }
+            CaptureKind::ActiveWindow => {
+                let id = active_window_id().context("xdotool found no active window")?;
+                cmd.args(["--window", &id]);
+            }
+            CaptureKind::Screen | CaptureKind::Output => {}
+        }
+        // maim writes png whatever the name, so ask for jpg when the name says so
+        let format = match Format::from_path(req.dest) {
+            Some(Format::Jpg) => "jpg",
+            _ => "png",
+        };
+        cmd.args(["--format", format]).arg(req.dest);
+        Ok(cmd)
+    }
+
+    /// maim exits 1 and says "Selection was cancelled" when slop is dismissed
+    fn cancelled(&self, kind: CaptureKind, output: &Output) -> bool {
+        matches!(kind, CaptureKind::Area)
+            && output.status.code() == Some(1)
+            && String::from_utf8_lossy(&output.stderr).contains("cancelled")
+    }
+
+    fn clipboard(&self, mime: &str) -> Command {
+        let mut cmd = Command::new("xclip");
+        cmd.args(["-selection", "clipboard", "-t", mime]);
+        cmd
+    }
+
+    fn wayland(&self) -> bool {
+        false
+    }
+}
+
+/// `xdotool getactivewindow`, or `None` if it is missing or nothing has focus
+fn active_window_id() -> Option<String> {
+    let out = Command::new("xdotool")
+        .arg("getactivewindow")
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let id = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (out.status.success() && !id.is_empty()).then_some(id)
+}
@@ -0,0 +1,72 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{env, fs, io::ErrorKind, path::PathBuf};
+
+use crate::{SaveHow, backend, format::Format, home};
+
+/// What to do when no mode flag is given on the command line
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    #[default]
+    Interactive,
+    Instant,
+    InstantArea,
+    InstantOutput,
+}
+
+/// Defaults read from `~/.config/crabture/config.toml`; CLI flags win over these
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub format: Option<Format>,
+    pub dir: Option<PathBuf>,
+    pub rofi_config: Option<PathBuf>,
+    pub name_template: Option<String>,
+    pub iso_names: bool,
+    pub mode: Option<Mode>,
+    pub save_mode: Option<SaveHow>,
+    pub backend: Option<backend::Kind>,
+    pub delay: Option<u64>,
+    pub countdown_interval: Option<u64>,
+    pub timer_presets: Vec<String>,
+    pub save_dirs: Vec<PathBuf>,
+    pub editor: Option<String>,
+    pub pin_viewer: Option<String>,
+    pub upload_url: Option<String>,
+    pub clipboard_cmd: Option<String>,
+    pub share_size: Option<u32>,
+    pub share_quality: Option<u8>,
+    pub subdir_template: Option<String>,
+    pub utc: bool,
+    pub strings: Option<PathBuf>,
+    pub lang: Option<String>,
+}
+
+impl Config {
+    /// Load the config file, treating a missing file as empty defaults
+    pub fn load() -> Result<Self> {
+        let path = config_path();
+        let raw = match fs::read_to_string(&path) {
+            Ok(s) => s,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => {
+                return Err(e).with_context(|| format!("reading config {}", path.display()));
+            }
+        };
+        toml::from_str(&raw).with_context(|| format!("parsing config {}", path.display()))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/crabture`, defaulting to `~/.config/crabture`
+pub fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home().join(".config"))
+        .join("crabture")
+}
+
+fn config_path() -> PathBuf {
+    config_dir().join("config.toml")
+}
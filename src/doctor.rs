@@ -0,0 +1,166 @@
+use anyhow::{Result, bail};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+use which::which;
+
+use crate::{CaptureKind, backend::Backend, ensure_writable, tools::Tools};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// Collects and prints the checklist line by line
+struct Report {
+    failed: usize,
+}
+
+impl Report {
+    fn line(&mut self, status: Status, what: &str, detail: &str) {
+        let tag = match status {
+            Status::Pass => "[ ok ]",
+            Status::Warn => "[warn]",
+            Status::Fail => {
+                self.failed += 1;
+                "[FAIL]"
+            }
+        };
+        println!("{tag} {what:<20} {detail}");
+    }
+
+    /// Whether `tool` is on PATH; missing is `missing` (warn or fail) with `why`
+    fn tool(&mut self, tool: &Path, missing: Status, why: &str) {
+        match which(tool) {
+            Ok(path) => self.line(
+                Status::Pass,
+                &tool.to_string_lossy(),
+                &path.to_string_lossy(),
+            ),
+            Err(_) => self.line(
+                missing,
+                &tool.to_string_lossy(),
+                &format!("not found, {why}"),
+            ),
+        }
+    }
+
+    /// The first of `tools` on PATH, or a warning naming all of them
+    fn any_tool(&mut self, what: &str, tools: &[&str], why: &str) {
+        match tools.iter().find_map(|t| which(t).ok()) {
+            Some(path) => self.line(Status::Pass, what, &path.to_string_lossy()),
+            None => self.line(
+                Status::Warn,
+                what,
+                &format!("none of {} found, {why}", tools.join(", ")),
+            ),
+        }
+    }
+}
+
+/// `crabture doctor`: check everything a capture depends on and fail if
+/// anything essential is broken
+pub fn run(tools: &Tools, backend: &dyn Backend, shot_dir: Result<PathBuf>) -> Result<()> {
+    let mut report = Report { failed: 0 };
+
+    let var = |name| env::var(name).unwrap_or_default();
+    let session = match var("XDG_SESSION_TYPE") {
+        s if !s.is_empty() => s,
+        _ if !var("WAYLAND_DISPLAY").is_empty() => "wayland".into(),
+        _ if !var("DISPLAY").is_empty() => "x11".into(),
+        _ => String::new(),
+    };
+    match session.as_str() {
+        "" => report.line(Status::Warn, "session", "no WAYLAND_DISPLAY or DISPLAY set"),
+        s => report.line(
+            Status::Pass,
+            "session",
+            &format!("{s}, {} backend", backend.name()),
+        ),
+    }
+
+    for tool in backend.required(CaptureKind::Screen, false) {
+        report.tool(&tool, Status::Fail, "needed for every capture");
+    }
+    report.tool(&tools.rofi, Status::Warn, "needed for the interactive menu");
+    let clipboard = backend.clipboard("text/plain");
+    report.tool(
+        Path::new(clipboard.get_program()),
+        Status::Warn,
+        "copying won't work",
+    );
+    if backend.wayland() {
+        report.tool(
+            Path::new("slurp"),
+            Status::Warn,
+            "area selection won't work",
+        );
+        report.tool(
+            Path::new("hyprpicker"),
+            Status::Warn,
+            "the screen isn't frozen while selecting",
+        );
+        report.tool(
+            Path::new("hyprctl"),
+            Status::Warn,
+            "no output or window lookups outside Hyprland",
+        );
+    }
+    report.tool(Path::new("cwebp"), Status::Warn, "webp output won't work");
+    report.any_tool(
+        "png optimizer",
+        &["oxipng", "optipng"],
+        "--optimize is skipped",
+    );
+    report.tool(Path::new("xdg-open"), Status::Warn, "--open won't work");
+    report.any_tool("sound player", &["paplay", "pw-play"], "--sound is silent");
+
+    match env::var("XDG_SCREENSHOTS_DIR") {
+        Ok(dir) => report.line(Status::Pass, "XDG_SCREENSHOTS_DIR", &dir),
+        Err(_) => report.line(
+            Status::Warn,
+            "XDG_SCREENSHOTS_DIR",
+            "not set, falling back to user-dirs.dirs or ~/Pictures",
+        ),
+    }
+
+    match shot_dir.and_then(|dir| ensure_writable(&dir, false).map(|()| dir)) {
+        Ok(dir) => report.line(Status::Pass, "screenshot dir", &dir.to_string_lossy()),
+        Err(e) => report.line(Status::Fail, "screenshot dir", &format!("{e:#}")),
+    }
+
+    if which(&tools.notify_send).is_err() {
+        report.line(
+            Status::Warn,
+            "notifications",
+            "notify-send not found, run with --no-notify",
+        );
+    } else {
+        let sent = Command::new(&tools.notify_send)
+            .args(["-t", "2000", "crabture doctor", "Notifications work"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match sent {
+            Ok(status) if status.success() => {
+                report.line(Status::Pass, "notifications", "test notification sent")
+            }
+            Ok(status) => report.line(
+                Status::Warn,
+                "notifications",
+                &format!("notify-send failed ({status})"),
+            ),
+            Err(e) => report.line(Status::Warn, "notifications", &e.to_string()),
+        }
+    }
+
+    match report.failed {
+        0 => Ok(()),
+        1 => bail!("1 essential check failed"),
+        n => bail!("{n} essential checks failed"),
+    }
+}
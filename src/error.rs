@@ -0,0 +1,20 @@
+use std::process::ExitStatus;
+use thiserror::Error;
+
+/// Failures `main` treats specially instead of printing them as errors
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    /// The user pressed Escape in a menu or aborted the area selection
+    #[error("cancelled")]
+    Cancelled,
+    /// rofi ran but exited with something other than its cancel code
+    #[error("rofi failed ({0})")]
+    Rofi(ExitStatus),
+}
+
+impl CaptureError {
+    /// Whether `err` (or anything it wraps) is a deliberate cancel
+    pub fn is_cancelled(err: &anyhow::Error) -> bool {
+        matches!(err.downcast_ref(), Some(CaptureError::Cancelled))
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{Context, Result, bail};
+use image::{DynamicImage, ImageFormat};
+use log::{debug, warn};
+use serde::Deserialize;
+use std::{
+    fmt, fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+    str::FromStr,
+};
+use which::which;
+
+/// Output image format; grimblast always captures png, the rest is converted
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Format {
+    Png,
+    Jpg,
+    Webp,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Png, Format::Jpg, Format::Webp];
+
+    pub fn ext(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpg => "jpg",
+            Format::Webp => "webp",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Format::Png => "image/png",
+            Format::Jpg => "image/jpeg",
+            Format::Webp => "image/webp",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "png" => Ok(Format::Png),
+            "jpg" | "jpeg" => Ok(Format::Jpg),
+            "webp" => Ok(Format::Webp),
+            _ => {
+                let valid: Vec<_> = Format::ALL.iter().map(|f| f.ext()).collect();
+                bail!(
+                    "unknown format {s:?}, expected one of: {}",
+                    valid.join(", ")
+                )
+            }
+        }
+    }
+}
+
+impl TryFrom<String> for Format {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.ext())
+    }
+}
+
+/// Decode the capture at `path` for the step named by `what`. The content is
+/// sniffed, since grimblast may hand us png bytes behind a .jpg name
+pub fn load(path: &Path, what: &str) -> Result<DynamicImage> {
+    Ok(load_as(path, what)?.0)
+}
+
+/// Like [`load`], also returning the format the file really is
+pub fn load_as(path: &Path, what: &str) -> Result<(DynamicImage, ImageFormat)> {
+    let reader = image::ImageReader::open(path)
+        .and_then(|r| r.with_guessed_format())
+        .with_context(|| format!("opening capture for {what}"))?;
+    let format = reader.format().unwrap_or(ImageFormat::Png);
+    let img = reader
+        .decode()
+        .with_context(|| format!("decoding capture for {what}"))?;
+    Ok((img, format))
+}
+
+/// Write `img` over `path` as png; `what` describes it for error messages
+pub fn save_png(path: &Path, img: impl Into<DynamicImage>, what: &str) -> Result<()> {
+    save(path, img, ImageFormat::Png, what)
+}
+
+/// Write `img` over `path` in `format`
+pub fn save(
+    path: &Path,
+    img: impl Into<DynamicImage>,
+    format: ImageFormat,
+    what: &str,
+) -> Result<()> {
+    let img = img.into();
+    let mut out = std::io::BufWriter::new(fs::File::create(path)?);
+    match format {
+        // jpeg has no alpha channel to write
+        ImageFormat::Jpeg => img.to_rgb8().write_to(&mut out, format),
+        _ => img.write_to(&mut out, format),
+    }
+    .with_context(|| format!("encoding {what} capture"))?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Decode `path` fully and throw the pixels away, to catch truncated files
+pub fn verify(path: &Path) -> Result<()> {
+    load(path, "verification")?;
+    Ok(())
+}
+
+pub fn reencode_jpeg(path: &Path, quality: u8) -> Result<()> {
+    let img = load(path, "re-encoding")?;
+    save_jpeg(path, &img, quality)
+}
+
+/// Write `img` to `path` as a jpg of the given quality
+pub fn save_jpeg(path: &Path, img: &DynamicImage, quality: u8) -> Result<()> {
+    let mut out = std::io::BufWriter::new(fs::File::create(path)?);
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut out, quality)
+        .encode_image(&img.to_rgb8())
+        .context("encoding jpg")?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Re-encode a png capture from its pixels alone, dropping every text, EXIF
+/// or other ancillary chunk. Costs a full decode and encode, which for a large
+/// screen is a good part of a second
+pub fn strip_png(path: &Path) -> Result<()> {
+    let img = load(path, "stripping metadata")?;
+    save_png(path, img, "stripped")
+}
+
+/// Downscale a png capture in place to fit `max_w` x `max_h`; images that
+/// already fit are left alone
+pub fn fit_within(path: &Path, max_w: u32, max_h: u32) -> Result<()> {
+    let img = load(path, "resizing")?;
+    if let Some(small) = shrink(&img, max_w, max_h) {
+        save_png(path, small, "resized")?;
+    }
+    Ok(())
+}
+
+/// `img` scaled down to fit `max_w` x `max_h`, or `None` if it already fits
+pub fn shrink(img: &DynamicImage, max_w: u32, max_h: u32) -> Option<DynamicImage> {
+    if img.width() <= max_w && img.height() <= max_h {
+        return None;
+    }
+    let small = img.resize(max_w, max_h, image::imageops::FilterType::Lanczos3);
+    debug!(
+        "resized {}x{} to {}x{}",
+        img.width(),
+        img.height(),
+        small.width(),
+        small.height()
+    );
+    Some(small)
+}
+
+/// Convert a png capture to webp with cwebp; lossless unless a quality is given
+pub fn png_to_webp(src: &Path, dest: &Path, quality: Option<u8>) -> Result<()> {
+    let mut cmd = Command::new("cwebp");
+    cmd.arg("-quiet");
+    match quality {
+        Some(q) => cmd.args(["-q", &q.to_string()]),
+        None => cmd.arg("-lossless"),
+    };
+    let status = cmd
+        .arg(src)
+        .arg("-o")
+        .arg(dest)
+        .stdout(Stdio::null())
+        .status()
+        .context("running cwebp")?;
+    if !status.success() {
+        bail!("cwebp failed ({status})");
+    }
+    Ok(())
+}
+
+/// Losslessly shrink a png in place with whichever optimizer is installed.
+/// Best effort: on any problem the original file is left as it was
+pub fn optimize_png(path: &Path) {
+    let Some((tool, args)) = [
+        ("oxipng", &["-q", "-o", "2"][..]),
+        ("optipng", &["-quiet", "-o2"][..]),
+    ]
+    .into_iter()
+    .find(|(tool, _)| which(tool).is_ok()) else {
+        warn!("--optimize needs oxipng or optipng in PATH, skipping");
+        return;
+    };
+    debug!("optimizing with {tool}");
+    match Command::new(tool).args(args).arg(path).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => warn!("{tool} failed ({status}), keeping the original"),
+        Err(e) => warn!("running {tool}: {e}"),
+    }
+}
@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use std::{
+    fs,
+    fs::OpenOptions,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::state;
+
+/// One saved screenshot, stored as a tab-separated line
+pub struct Entry {
+    pub timestamp: String,
+    pub kind: String,
+    pub mode: String,
+    pub path: PathBuf,
+}
+
+impl Entry {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}",
+            self.timestamp,
+            self.kind,
+            self.mode,
+            self.path.display()
+        )
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut parts = line.splitn(4, '\t');
+        Some(Self {
+            timestamp: parts.next()?.to_string(),
+            kind: parts.next()?.to_string(),
+            mode: parts.next()?.to_string(),
+            path: PathBuf::from(parts.next()?),
+        })
+    }
+}
+
+/// `history.log` in the state dir
+pub fn path() -> PathBuf {
+    state::dir().join("history.log")
+}
+
+/// Append an entry, then drop the oldest lines beyond `limit`
+pub fn append(entry: &Entry, limit: Option<usize>) -> Result<()> {
+    let path = path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("opening {}", path.display()))?;
+    writeln!(file, "{}", entry.to_line())?;
+    drop(file);
+
+    if let Some(limit) = limit {
+        trim(&path, limit)?;
+    }
+    Ok(())
+}
+
+fn trim(path: &Path, limit: usize) -> Result<()> {
+    let raw = fs::read_to_string(path)?;
+    let lines: Vec<&str> = raw.lines().collect();
+    if lines.len() <= limit {
+        return Ok(());
+    }
+    let mut kept = lines[lines.len() - limit..].join("\n");
+    kept.push('\n');
+    fs::write(path, kept).with_context(|| format!("trimming {}", path.display()))
+}
+
+/// All entries, oldest first; a missing log is just empty
+pub fn read() -> Result<Vec<Entry>> {
+    let path = path();
+    match fs::read_to_string(&path) {
+        Ok(raw) => Ok(raw.lines().filter_map(Entry::parse).collect()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(e).with_context(|| format!("reading {}", path.display())),
+    }
+}
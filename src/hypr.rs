@@ -0,0 +1,166 @@
+use anyhow::{Context, Result, bail};
+use log::warn;
+use serde::Deserialize;
+use std::process::{Command, Stdio};
+
+/// One entry of `hyprctl monitors -j`
+#[derive(Debug, Deserialize)]
+pub struct Monitor {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default = "unit_scale")]
+    pub scale: f64,
+    #[serde(default)]
+    pub focused: bool,
+}
+
+fn unit_scale() -> f64 {
+    1.0
+}
+
+pub fn monitors() -> Result<Vec<Monitor>> {
+    let out = Command::new("hyprctl")
+        .args(["monitors", "-j"])
+        .stderr(Stdio::null())
+        .output()
+        .context("running hyprctl")?;
+    if !out.status.success() {
+        bail!("hyprctl monitors failed");
+    }
+    serde_json::from_slice(&out.stdout).context("parsing hyprctl monitors output")
+}
+
+/// Whether monitors use different scale factors, where the hyprpicker freeze
+/// is known to misalign slurp's overlay; false if hyprctl isn't available
+pub fn mixed_scaling() -> bool {
+    let Ok(monitors) = monitors() else {
+        return false;
+    };
+    monitors
+        .windows(2)
+        .any(|pair| (pair[0].scale - pair[1].scale).abs() > f64::EPSILON)
+}
+
+/// Geometry of the focused window from `hyprctl activewindow -j`
+#[derive(Debug, Deserialize)]
+pub struct Window {
+    pub at: (i32, i32),
+    pub size: (u32, u32),
+}
+
+/// The focused window, or `None` when nothing has focus (hyprctl prints `{}`)
+pub fn active_window() -> Result<Option<Window>> {
+    let out = Command::new("hyprctl")
+        .args(["activewindow", "-j"])
+        .stderr(Stdio::null())
+        .output()
+        .context("running hyprctl")?;
+    if !out.status.success() {
+        bail!("hyprctl activewindow failed");
+    }
+    #[derive(Deserialize)]
+    struct Raw {
+        at: Option<(i32, i32)>,
+        size: Option<(u32, u32)>,
+    }
+    let raw: Raw =
+        serde_json::from_slice(&out.stdout).context("parsing hyprctl activewindow output")?;
+    Ok(match (raw.at, raw.size) {
+        (Some(at), Some(size)) if size.0 > 0 && size.1 > 0 => Some(Window { at, size }),
+        _ => None,
+    })
+}
+
+/// Move focus to a monitor so grimblast's `output` target captures it
+pub fn focus_monitor(name: &str) -> Result<()> {
+    let status = Command::new("hyprctl")
+        .args(["dispatch", "focusmonitor", name])
+        .stdout(Stdio::null())
+        .status()
+        .context("running hyprctl")?;
+    if !status.success() {
+        bail!("hyprctl could not focus monitor {name}");
+    }
+    Ok(())
+}
+
+/// The monitor that had focus, which gets it back when this is dropped
+pub struct RestoreFocus(pub String);
+
+impl RestoreFocus {
+    /// Remember the focused monitor; `None` if hyprctl can't tell
+    pub fn save() -> Option<Self> {
+        monitors()
+            .ok()?
+            .into_iter()
+            .find(|m| m.focused)
+            .map(|m| Self(m.name))
+    }
+}
+
+impl Drop for RestoreFocus {
+    fn drop(&mut self) {
+        if let Err(e) = focus_monitor(&self.0) {
+            warn!("{e:#}");
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WlrOutput {
+    name: String,
+    enabled: bool,
+    modes: Vec<WlrMode>,
+    position: Option<WlrPosition>,
+}
+
+#[derive(Deserialize)]
+struct WlrMode {
+    width: u32,
+    height: u32,
+    current: bool,
+}
+
+#[derive(Deserialize)]
+struct WlrPosition {
+    x: i32,
+    y: i32,
+}
+
+/// Same as [`monitors`] but via `wlr-randr --json`, for non-Hyprland compositors
+pub fn wlr_randr_monitors() -> Result<Vec<Monitor>> {
+    let out = Command::new("wlr-randr")
+        .arg("--json")
+        .stderr(Stdio::null())
+        .output()
+        .context("running wlr-randr")?;
+    if !out.status.success() {
+        bail!("wlr-randr failed");
+    }
+    let outputs: Vec<WlrOutput> =
+        serde_json::from_slice(&out.stdout).context("parsing wlr-randr output")?;
+    Ok(outputs
+        .into_iter()
+        .filter(|o| o.enabled)
+        .map(|o| {
+            let (width, height) = o
+                .modes
+                .iter()
+                .find(|m| m.current)
+                .map_or((0, 0), |m| (m.width, m.height));
+            let (x, y) = o.position.map_or((0, 0), |p| (p.x, p.y));
+            Monitor {
+                name: o.name,
+                width,
+                height,
+                x,
+                y,
+                scale: 1.0,
+                focused: false,
+            }
+        })
+        .collect())
+}
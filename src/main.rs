@@ -1,18 +1,68 @@
+mod backend;
+mod config;
+mod doctor;
+mod error;
+mod format;
+mod history;
+mod hypr;
+mod metadata;
+mod record;
+mod redact;
+mod shadow;
+mod stamp;
+mod state;
+mod strings;
+mod tools;
+mod upload;
+
 use anyhow::{Context, Result, bail};
-use clap::Parser;
+use clap::{ArgAction, ArgGroup, CommandFactory, Parser, Subcommand, ValueEnum};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use std::{
+    borrow::Cow,
     env, fs,
     io::Write,
     path::{Path, PathBuf},
-    process::{Command, Stdio},
+    process::{Child, Command, ExitCode, ExitStatus, Stdio},
+    rc::Rc,
+    sync::{
+        Once,
+        atomic::{AtomicBool, Ordering},
+    },
     thread::sleep,
-    time::Duration,
+    time::{Duration, Instant},
 };
-use time::OffsetDateTime;
+use tempfile::TempPath;
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
 use which::which;
 
+use backend::Backend;
+use config::{Config, Mode};
+use error::CaptureError;
+use format::Format;
+use state::LastChoice;
+use strings::Strings;
+use tools::Tools;
+
+/// Longest `--delay` (or config `delay`) accepted, in seconds
+const MAX_DELAY: u64 = 3600;
+
 #[derive(Parser, Debug)]
-#[command(version, about = "Rusty screenshot helper (grimblast + rofi)")]
+#[command(
+    version,
+    about = "Rusty screenshot helper (grimblast + rofi)",
+    after_help = "Exit status: 0 on success, 1 on error, 2 when a menu or selection is cancelled"
+)]
+#[command(group(ArgGroup::new("run_mode").args([
+    "instant",
+    "instant_area",
+    "instant_output",
+    "window",
+    "area_edit",
+    "all_outputs",
+    "interactive",
+])))]
 struct Cli {
     /// Take immediate full-screen shot (no UI)
     #[arg(long)]
@@ -22,30 +72,419 @@ struct Cli {
     #[arg(long)]
     instant_area: bool,
 
-    /// Use interactive rofi flow
+    /// With --instant-area, keep selecting and saving regions until cancelled
+    #[arg(long, requires = "instant_area")]
+    repeat: bool,
+
+    /// Take immediate shot of the active display (no UI)
+    #[arg(long)]
+    instant_output: bool,
+
+    /// Capture every monitor into its own file, named after the output (no UI)
+    #[arg(long, conflicts_with = "output")]
+    all_outputs: bool,
+
+    /// Record a video with wf-recorder instead of taking a screenshot, of the
+    /// focused output or whatever --instant-area, --instant-output or --window
+    /// pick; run it again (or press Ctrl-C) to stop
+    #[arg(long, conflicts_with_all = ["all_outputs", "interactive", "area_edit", "repeat"])]
+    record: bool,
+
+    /// Record this many seconds (up to a minute) and save them as a gif, of an
+    /// area unless --instant, --instant-output or --window say otherwise.
+    /// Needs wf-recorder and ffmpeg; gifski, if installed, makes smaller files
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(1..=60),
+        conflicts_with_all = ["record", "all_outputs", "interactive", "area_edit", "repeat"]
+    )]
+    gif: Option<u64>,
+
+    /// Take immediate shot of the focused window (no UI)
+    #[arg(long)]
+    window: bool,
+
+    /// Select an area and open it in the editor, then save it (no UI)
+    #[arg(long)]
+    area_edit: bool,
+
+    /// Wait this many seconds (with the usual countdown) before capturing; the
+    /// rofi flow then skips its "Immediate/Delayed" and timer menus
+    #[arg(
+        long,
+        value_name = "SECS",
+        value_parser = clap::value_parser!(u64).range(..=MAX_DELAY)
+    )]
+    delay: Option<u64>,
+
+    /// Use interactive rofi flow, even if the config sets another mode
     #[arg(long)]
     interactive: bool,
 
-    /// Screenshot directory (default: XDG_SCREENSHOTS_DIR or ~/Pictures)
+    /// Screenshot directory (default: CRABTURE_DIR, XDG_SCREENSHOTS_DIR or ~/Pictures)
     dir: Option<PathBuf>,
 
-    /// Image format: png or jpg
-    #[arg(long, default_value = "png")]
-    format: String,
+    /// Also save into this directory; repeatable. Without the positional DIR the
+    /// first one becomes the main screenshot directory
+    #[arg(long = "dir", value_name = "DIR")]
+    dirs: Vec<PathBuf>,
+
+    /// Image format: png, jpg or webp (webp needs cwebp) [default: png]
+    #[arg(long)]
+    format: Option<Format>,
+
+    /// Print the versions of crabture and the tools it runs, for bug reports,
+    /// and exit
+    #[arg(long)]
+    versions: bool,
+
+    /// Print the supported image formats and exit
+    #[arg(long)]
+    list_formats: bool,
+
+    /// Quality 0-100 for jpg (re-encoded) or webp (lossy instead of lossless).
+    /// Without it jpg files are kept exactly as grimblast wrote them
+    #[arg(long, value_name = "0-100", value_parser = clap::value_parser!(u8).range(0..=100))]
+    quality: Option<u8>,
+
+    /// Print the absolute path of the saved file to stdout
+    #[arg(long)]
+    print_path: bool,
+
+    /// Print a JSON summary of the capture to stdout
+    #[arg(long, conflicts_with = "print_path")]
+    json: bool,
+
+    /// Don't send any notifications (crabture's own or grimblast's)
+    #[arg(long, visible_alias = "quiet")]
+    no_notify: bool,
+
+    /// Retry grimblast this many times if it fails
+    #[arg(long, default_value_t = 0)]
+    retries: u32,
+
+    /// Play a sound after capturing; "default" uses the freedesktop shutter sound
+    #[arg(long, value_name = "PATH")]
+    sound: Option<PathBuf>,
+
+    /// Copy the saved file's path to the clipboard with wl-copy
+    #[arg(long)]
+    copy_path: bool,
+
+    /// Freeze the screen with hyprpicker for every capture kind, not just area
+    #[arg(long)]
+    freeze: bool,
+
+    /// Never freeze the screen. By default area captures freeze, except on
+    /// setups with mixed monitor scaling where the frozen overlay misaligns
+    #[arg(long, conflicts_with = "freeze")]
+    no_freeze: bool,
+
+    /// Milliseconds to keep the screen frozen after grimblast returns
+    #[arg(long, default_value_t = 0)]
+    unfreeze_delay_ms: u64,
+
+    /// Monitor to capture in output mode (see `hyprctl monitors`) instead of the focused one
+    #[arg(long, value_name = "NAME")]
+    output: Option<String>,
+
+    /// Print the available monitors and exit
+    #[arg(long)]
+    list_outputs: bool,
+
+    /// Show the delay as one big countdown notification updated in place
+    #[arg(long)]
+    overlay_countdown: bool,
+
+    /// Seconds between countdown notifications before the final 3, 2, 1
+    /// [default: 5]
+    #[arg(long, value_name = "SECS", value_parser = clap::value_parser!(u64).range(1..))]
+    countdown_interval: Option<u64>,
+
+    /// Print the grimblast command instead of running it
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Notification timeout in milliseconds; 0 uses the notification daemon's default
+    #[arg(long, value_name = "MS", default_value_t = 1000)]
+    notify_timeout: u32,
+
+    /// Add Open and Copy buttons to the save notification; crabture keeps
+    /// running until the notification is clicked or dismissed. Pair it with a
+    /// longer --notify-timeout (or 0) to leave time to click
+    #[arg(long)]
+    notify_actions: bool,
+
+    /// Open the saved screenshot in the default image viewer
+    #[arg(long)]
+    open: bool,
+
+    /// Show the saved screenshot in the file manager
+    #[arg(long)]
+    reveal: bool,
+
+    /// For area captures, grab the whole screen first and pick the area over
+    /// the frozen screen afterwards, so nothing changes between the two
+    #[arg(long)]
+    pick_after: bool,
+
+    /// Don't write the capture time, output, geometry and tool versions into the
+    /// saved png (text chunks) or jpg (EXIF)
+    #[arg(long)]
+    no_metadata: bool,
+
+    /// Re-encode the capture to drop any metadata the backend or compositor put
+    /// in it (and skip adding crabture's own). Costs a full decode and encode,
+    /// and for jpg a second lossy pass unless --quality already re-encodes it;
+    /// webp from cwebp carries none to begin with
+    #[arg(long)]
+    strip_metadata: bool,
+
+    /// After capturing, select rectangles with slurp (Escape when done) to hide
+    /// tokens, emails and the like before saving
+    #[arg(long)]
+    redact: bool,
+
+    /// How --redact hides the selected rectangles
+    #[arg(long, value_enum, default_value_t)]
+    redact_style: redact::Style,
+
+    /// Keep the saved screenshot on screen in a viewer window (save and
+    /// copy & save only); make it float and pin it with a window rule
+    #[arg(long)]
+    pin: bool,
+
+    /// Viewer for --pin, same {} syntax as --editor [default: imv, else feh]
+    #[arg(long, value_name = "CMD")]
+    pin_viewer: Option<String>,
+
+    /// Editor for edit mode instead of grimblast's, e.g. "swappy -f {} -o {}"
+    #[arg(long, value_name = "CMD")]
+    editor: Option<String>,
+
+    /// Write the image to stdout instead of saving it (implies --no-notify)
+    #[arg(long, conflicts_with_all = ["json", "print_path"])]
+    stdout: bool,
+
+    /// Shell command to run on the saved file, `{}` is replaced by its path
+    /// (skipped in copy-only mode, where no file is kept)
+    #[arg(long, value_name = "CMD")]
+    exec: Option<String>,
+
+    /// Upload the saved file and copy the returned URL to the clipboard
+    #[arg(long)]
+    upload: bool,
+
+    /// Clipboard command that reads the data on stdin, instead of wl-copy (or
+    /// xclip on X11); `{mime}` becomes the MIME type, e.g. "xclip -sel c -t {mime}"
+    #[arg(long, value_name = "CMD")]
+    clipboard_cmd: Option<String>,
+
+    /// Endpoint for --upload, taking a multipart `file` field [default: https://0x0.st]
+    #[arg(long, value_name = "URL")]
+    upload_url: Option<String>,
+
+    /// Print the log of saved screenshots and exit
+    #[arg(long)]
+    history: bool,
+
+    /// Keep at most this many entries in the history log
+    #[arg(long, value_name = "N")]
+    history_limit: Option<usize>,
+
+    /// Copy the most recent screenshot to the clipboard and exit
+    #[arg(long)]
+    copy_last: bool,
+
+    /// TOML file overriding the rofi menu labels (for translations)
+    #[arg(long, value_name = "FILE")]
+    strings: Option<PathBuf>,
+
+    /// Load menu labels from <config dir>/crabture/lang/<LANG>.toml
+    #[arg(long, conflicts_with = "strings")]
+    lang: Option<String>,
+
+    /// Run oxipng/optipng over png captures (best effort)
+    #[arg(long)]
+    optimize: bool,
+
+    /// Include the mouse pointer in the capture
+    #[arg(long)]
+    cursor: bool,
+
+    /// Leave the mouse pointer out of the capture (grimblast's default)
+    #[arg(long, conflicts_with = "cursor")]
+    no_cursor: bool,
+
+    /// Capture engine: grimblast on Wayland or maim on X11, or grim and slurp
+    /// without grimblast [default: auto]
+    #[arg(long, value_name = "BACKEND")]
+    backend: Option<backend::Kind>,
+
+    /// Extra argument for grimblast, passed verbatim ahead of the action and
+    /// the positional filename; repeat for more (e.g. --grimblast-arg=--wait
+    /// --grimblast-arg=2)
+    #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+    grimblast_arg: Vec<String>,
+
+    /// Decode each capture before saving it, to catch truncated or corrupt files
+    #[arg(long)]
+    verify: bool,
+
+    /// Downscale captures wider than this, keeping the aspect ratio
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    max_width: Option<u32>,
+
+    /// Downscale captures taller than this, keeping the aspect ratio
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    max_height: Option<u32>,
+
+    /// Also write a small jpg of each saved screenshot into a shared/ subdir
+    /// and copy its path, for sending while the full-size file stays archived
+    #[arg(long)]
+    share_copy: bool,
+
+    /// Longest side of the --share-copy image [default: 1280]
+    #[arg(long, value_name = "PX", value_parser = clap::value_parser!(u32).range(1..))]
+    share_size: Option<u32>,
+
+    /// jpg quality of the --share-copy image [default: 75]
+    #[arg(long, value_name = "1-100", value_parser = clap::value_parser!(u8).range(1..=100))]
+    share_quality: Option<u8>,
+
+    /// Stamp this label in a corner of saved images; template tokens such as
+    /// {year} or {hour} are expanded
+    #[arg(long, value_name = "TEXT")]
+    stamp: Option<String>,
+
+    /// Corner for --stamp
+    #[arg(long, value_enum, default_value_t, requires = "stamp")]
+    stamp_pos: stamp::Position,
+
+    /// Frame window and output captures with a drop shadow on a transparent canvas
+    #[arg(long)]
+    shadow: bool,
+
+    /// Transparent margin around the image for --shadow, in pixels
+    #[arg(long, value_name = "PX", default_value_t = 40)]
+    shadow_padding: u32,
+
+    /// Blur radius of the --shadow, in pixels
+    #[arg(long, value_name = "PX", default_value_t = 20.0)]
+    shadow_blur: f32,
+
+    /// Shadow color as #rrggbb or #rrggbbaa
+    #[arg(long, value_name = "HEX", default_value = "#00000099", value_parser = shadow::parse_color)]
+    shadow_color: [u8; 4],
+
+    /// Round the image corners by this many pixels with --shadow
+    #[arg(long, value_name = "PX", default_value_t = 0)]
+    corner_radius: u32,
+
+    /// Region selector to use instead of slurp for area captures, e.g.
+    /// "slurp -b 00000080 -c ff7f50"; it must print a slurp-style geometry
+    #[arg(long, value_name = "CMD")]
+    selector: Option<String>,
+
+    /// Border color of the slurp selection box, e.g. "#ff7f50ff"
+    #[arg(long, value_name = "COLOR", conflicts_with = "selector")]
+    slurp_color: Option<String>,
+
+    /// Border width of the slurp selection box, in pixels
+    #[arg(long, value_name = "PX", conflicts_with = "selector")]
+    slurp_border: Option<u32>,
+
+    /// Color dimming everything outside the slurp selection, e.g. "#00000080"
+    #[arg(long, value_name = "COLOR", conflicts_with = "selector")]
+    slurp_bg: Option<String>,
+
+    /// Log what crabture is doing to stderr (-v debug, -vv trace)
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Use UTC instead of the local time zone for file names and history
+    #[arg(long)]
+    utc: bool,
+
+    /// What the interactive flow does with the capture, skipping the "How to
+    /// save" menu
+    #[arg(long, value_name = "MODE")]
+    save_mode: Option<SaveHow>,
+
+    /// Show the "How to save" menu even when a save mode is configured
+    #[arg(long)]
+    ask_save: bool,
+
+    /// Choices for the "Delayed" menu, e.g. "3s,15s" (default 5s,10s,20s,30s,60s)
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    timer_presets: Vec<String>,
+
+    /// Directories to pick from in the interactive menu, e.g.
+    /// "~/Pictures/Work,~/Pictures/Personal"; one or none skips the menu
+    #[arg(long, value_name = "LIST", value_delimiter = ',')]
+    save_dirs: Vec<PathBuf>,
 
     /// Optional rofi config path
     #[arg(long)]
     rofi_config: Option<PathBuf>,
+
+    /// Extra argument for every rofi menu, after crabture's own; repeat for
+    /// more (e.g. --rofi-arg=-theme-str --rofi-arg='window {width: 20em;}')
+    #[arg(long, value_name = "ARG", allow_hyphen_values = true)]
+    rofi_arg: Vec<String>,
+
+    /// Save into a YYYY-MM subfolder of the screenshot directory
+    #[arg(long)]
+    subdir_by_date: bool,
+
+    /// Subfolder template, same tokens as --name-template (implies --subdir-by-date)
+    #[arg(long, value_name = "TEMPLATE")]
+    subdir_template: Option<String>,
+
+    /// File name template, e.g. "{year}-{month}-{day}_{kind}_{seq}"; a trailing
+    /// .png/.jpg/.webp picks the format
+    /// (tokens: year, month, day, hour, minute, second, kind, seq)
+    #[arg(long)]
+    name_template: Option<String>,
+
+    /// Sortable names like screenshot_2024-05-01T13-37-00.png instead of the
+    /// default day-first timestamp
+    #[arg(long, conflicts_with = "name_template")]
+    iso_names: bool,
+
+    /// Replace an existing file with the same name instead of adding a -1, -2
+    /// suffix
+    #[arg(long)]
+    overwrite: bool,
+
+    #[command(subcommand)]
+    command: Option<CliCommand>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CliCommand {
+    /// Print a shell completion script to stdout
+    Completions {
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print a roff man page to stdout
+    Man,
+    /// Check the tools, session and screenshot dir a capture needs
+    Doctor,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum CaptureKind {
     Screen,
     Output,
     Area,
+    ActiveWindow,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
 enum SaveHow {
     Copy,
     Save,
@@ -53,250 +492,2480 @@ enum SaveHow {
     Edit,
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+impl SaveHow {
+    fn as_str(self) -> &'static str {
+        match self {
+            SaveHow::Copy => "copy",
+            SaveHow::Save => "save",
+            SaveHow::Copysave => "copysave",
+            SaveHow::Edit => "edit",
+        }
+    }
 
-    ensure_tools(&["grimblast", "rofi", "notify-send"])?;
+    fn copies(self) -> bool {
+        matches!(self, SaveHow::Copy | SaveHow::Copysave)
+    }
+}
 
-    let _ = which("slurp");
+/// Machine-readable result printed by `--json`
+#[derive(Serialize)]
+struct Report {
+    path: Option<PathBuf>,
+    /// `path` followed by the copies in extra `--dir`s
+    paths: Vec<PathBuf>,
+    kind: &'static str,
+    mode: &'static str,
+    format: &'static str,
+    timestamp: String,
+    clipboard: bool,
+    exec_status: Option<i32>,
+    url: Option<String>,
+}
 
-    let shot_dir = cli
-        .dir
-        .or_else(xdg_screenshots_dir)
-        .unwrap_or(home().join("Pictures"));
-    fs::create_dir_all(&shot_dir).ok();
+impl Cli {
+    /// What to capture straight away and how, or `None` for the rofi flow
+    fn instant_shot(&self) -> Option<(CaptureKind, SaveHow)> {
+        if self.instant {
+            Some((CaptureKind::Screen, SaveHow::Save))
+        } else if self.instant_area {
+            Some((CaptureKind::Area, SaveHow::Save))
+        } else if self.instant_output {
+            Some((CaptureKind::Output, SaveHow::Save))
+        } else if self.window {
+            Some((CaptureKind::ActiveWindow, SaveHow::Save))
+        } else if self.area_edit {
+            Some((CaptureKind::Area, SaveHow::Edit))
+        } else {
+            None
+        }
+    }
 
-    if cli.instant {
-        take(
-            CaptureKind::Screen,
-            SaveHow::Save,
-            &shot_dir,
-            &cli.format,
-            None,
-        )?;
+    /// Fill in anything not given on the command line from the config file
+    fn apply_config(&mut self, cfg: Config) {
+        if !(self.interactive
+            || self.all_outputs
+            || self.record
+            || self.gif.is_some()
+            || self.instant_shot().is_some())
+        {
+            match cfg.mode.unwrap_or_default() {
+                Mode::Interactive => self.interactive = true,
+                Mode::Instant => self.instant = true,
+                Mode::InstantArea => self.instant_area = true,
+                Mode::InstantOutput => self.instant_output = true,
+            }
+        }
+        // an explicit --dir is the answer the menu would ask for
+        if self.save_dirs.is_empty() && self.dir.is_none() && self.dirs.is_empty() {
+            self.save_dirs = cfg.save_dirs;
+        }
+        if self.dirs.is_empty() {
+            self.dir = self.dir.take().or(cfg.dir);
+        }
+        self.format = self.format.take().or(cfg.format);
+        self.rofi_config = self.rofi_config.take().or(cfg.rofi_config);
+        if !self.iso_names {
+            self.name_template = self.name_template.take().or(cfg.name_template);
+        }
+        self.iso_names |= cfg.iso_names;
+        self.editor = self.editor.take().or(cfg.editor);
+        self.pin_viewer = self.pin_viewer.take().or(cfg.pin_viewer);
+        self.upload_url = self.upload_url.take().or(cfg.upload_url);
+        self.clipboard_cmd = self.clipboard_cmd.take().or(cfg.clipboard_cmd);
+        self.subdir_template = self.subdir_template.take().or(cfg.subdir_template);
+        if cfg.countdown_interval == Some(0) {
+            warn!("ignoring countdown_interval = 0, it must be at least 1");
+        }
+        self.countdown_interval = self
+            .countdown_interval
+            .or(cfg.countdown_interval.filter(|&secs| secs > 0));
+        self.share_size = self.share_size.or(cfg.share_size);
+        self.share_quality = self.share_quality.or(cfg.share_quality);
+        self.utc |= cfg.utc;
+        self.save_mode = self.save_mode.or(cfg.save_mode);
+        self.backend = self.backend.or(cfg.backend);
+        if self.timer_presets.is_empty() {
+            self.timer_presets = cfg.timer_presets;
+        }
+        // a configured delay is only a menu shortcut; instant captures stay instant
+        if self.interactive {
+            self.delay = self.delay.or(cfg.delay);
+        }
+        // --strings and --lang pick the same thing, so either on the CLI wins
+        if self.strings.is_none() && self.lang.is_none() {
+            self.strings = cfg.strings;
+            self.lang = cfg.lang;
+        }
     }
+}
 
-    if cli.instant_area {
-        take(
-            CaptureKind::Area,
-            SaveHow::Save,
-            &shot_dir,
-            &cli.format,
-            None,
-        )?;
-        return Ok(());
+impl CaptureKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            CaptureKind::Screen => "screen",
+            CaptureKind::Output => "output",
+            CaptureKind::Area => "area",
+            CaptureKind::ActiveWindow => "active",
+        }
     }
+}
 
-    // default to interactive if nothing else was specified
-    run_interactive(&shot_dir, &cli.format, cli.rofi_config.as_deref())
+/// Resolved settings shared by the instant and interactive paths
+#[derive(Clone)]
+struct Options {
+    tools: Tools,
+    backend: Rc<dyn Backend>,
+    shot_dir: PathBuf,
+    extra_dirs: Vec<PathBuf>,
+    format: Format,
+    name_template: Option<String>,
+    quality: Option<u8>,
+    rofi_config: Option<PathBuf>,
+    rofi_args: Vec<String>,
+    utc: bool,
+    overwrite: bool,
+    /// `None`, or `--ask-save`, means ask in the menu
+    save_mode: Option<SaveHow>,
+    /// Skips the delay menus when set, `Some(0)` meaning immediate
+    delay: Option<u64>,
+    timer_presets: Vec<String>,
+    save_dirs: Vec<PathBuf>,
+    print_path: bool,
+    json: bool,
+    notify: bool,
+    notify_timeout: u32,
+    notify_actions: bool,
+    retries: u32,
+    sound: Option<PathBuf>,
+    copy_path: bool,
+    freeze: bool,
+    no_freeze: bool,
+    cursor: bool,
+    unfreeze_delay_ms: u64,
+    output: Option<String>,
+    overlay_countdown: bool,
+    countdown_interval: u64,
+    dry_run: bool,
+    open: bool,
+    reveal: bool,
+    editor: Option<String>,
+    redact: Option<redact::Style>,
+    metadata: bool,
+    strip_metadata: bool,
+    pick_after: bool,
+    /// `--pin`, with its viewer; empty picks imv or feh
+    pin: Option<String>,
+    stdout: bool,
+    exec: Option<String>,
+    upload: bool,
+    upload_url: Option<String>,
+    /// `--clipboard-cmd`, replacing the backend's clipboard tool
+    clipboard_cmd: Option<String>,
+    history_limit: Option<usize>,
+    strings: Strings,
+    optimize: bool,
+    subdir: Option<String>,
+    max_size: Option<(u32, u32)>,
+    /// `--share-copy`, as its longest side and jpg quality
+    share: Option<(u32, u8)>,
+    verify: bool,
+    stamp: Option<String>,
+    stamp_pos: stamp::Position,
+    shadow: Option<shadow::Shadow>,
+    selector: Option<String>,
 }
 
-fn run_interactive(shot_dir: &Path, format: &str, rofi_cfg: Option<&Path>) -> Result<()> {
-    let when = rofi_pick("Take screenshot", &["Immediate", "Delayed"], rofi_cfg)?;
-    let delay = if when == "Delayed" {
-        let t = rofi_pick(
-            "Choose timer",
-            &["5s", "10s", "20s", "30s", "60s"],
-            rofi_cfg,
-        )?;
-        t.trim_end_matches("s").parse::<u64>().unwrap_or(5)
-    } else {
-        0
-    };
-
-    let kind = match rofi_pick(
-        "Type of screenshot",
-        &[
-            "Capture Everything",
-            "Capture Active Display",
-            "Capture Selection",
-        ],
-        rofi_cfg,
-    )?
-    .as_str()
-    {
-        "Capture Everything" => CaptureKind::Screen,
-        "Capture Active Display" => CaptureKind::Output,
-        _ => CaptureKind::Area,
-    };
+/// A cancel exits quietly with status 2 so keybind wrappers can stay silent
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) if CaptureError::is_cancelled(&e) => {
+            debug!("{e:#}");
+            ExitCode::from(2)
+        }
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    let how = match rofi_pick(
-        "How to save",
-        &["Copy", "Save", "Copy & Save", "Edit"],
-        rofi_cfg,
-    )?
-    .as_str()
-    {
-        "Copy" => SaveHow::Copy,
-        "Save" => SaveHow::Save,
-        "Copy & Save" => SaveHow::Copysave,
-        _ => SaveHow::Edit,
-    };
+/// Write a generated script or man page to stdout; a reader that stops early,
+/// like `| head`, is not an error
+fn print_generated(bytes: &[u8]) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    match stdout.write_all(bytes).and_then(|()| stdout.flush()) {
+        Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => Ok(()),
+        written => written.context("writing to stdout"),
+    }
+}
+
+fn run() -> Result<()> {
+    let mut cli = Cli::parse();
+    init_logging(cli.verbose);
+    // these need nothing installed and no config, so they go before both
+    match cli.command {
+        Some(CliCommand::Completions { shell }) => {
+            let mut script = Vec::new();
+            clap_complete::generate(shell, &mut Cli::command(), "crabture", &mut script);
+            return print_generated(&script);
+        }
+        Some(CliCommand::Man) => {
+            let mut page = Vec::new();
+            clap_mangen::Man::new(Cli::command()).render(&mut page)?;
+            return print_generated(&page);
+        }
+        Some(CliCommand::Doctor) | None => {}
+    }
+    cli.apply_config(Config::load()?);
+    if let Some(secs) = cli.delay.filter(|&s| s > MAX_DELAY) {
+        bail!("delay must be at most {MAX_DELAY} seconds, got {secs}");
+    }
 
-    if delay > 0 {
-        countdown(delay)?;
+    if cli.versions {
+        print_versions(&Tools::from_env());
+        return Ok(());
+    }
+    if cli.list_outputs {
+        return list_outputs();
+    }
+    if cli.list_formats {
+        for format in Format::ALL {
+            println!("{format}");
+        }
+        return Ok(());
+    }
+    if cli.history {
+        for e in history::read()? {
+            println!(
+                "{}  {:<6} {:<8} {}",
+                e.timestamp,
+                e.kind,
+                e.mode,
+                e.path.display()
+            );
+        }
+        return Ok(());
     }
 
-    take(kind, how, shot_dir, format, rofi_cfg)
-}
+    if let Some(CliCommand::Doctor) = cli.command {
+        let tools = Tools::from_env();
+        let backend = backend::select(cli.backend.unwrap_or_default(), &tools, Vec::new());
+        let dir = resolve_shot_dir(cli.dir.or_else(|| cli.dirs.into_iter().next()));
+        return doctor::run(&tools, &*backend, dir);
+    }
 
-fn take(
-    kind: CaptureKind,
-    how: SaveHow,
-    shot_dir: &Path,
-    format: &str,
-    _rofi_cfg: Option<&Path>,
-) -> Result<()> {
-    let name = file_name(format);
-    let tmp_path = home().join(&name);
+    let instant = cli.instant_shot();
+    let mut dirs = cli.dirs.into_iter();
+    let shot_dir = resolve_shot_dir(cli.dir.or_else(|| dirs.next()))?;
+    ensure_writable(&shot_dir, cli.dry_run)?;
+    debug!("screenshot dir: {}", shot_dir.display());
+    let extra_dirs = dirs
+        .map(|d| {
+            let d = expand_path(&d)?;
+            ensure_writable(&d, cli.dry_run)?;
+            debug!("also saving to {}", d.display());
+            Ok(d)
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    // freeze screen for area selection if hyprpicker exists; let grimblast run slurp
-    let mut picker_child = if matches!(kind, CaptureKind::Area) && which("hyprpicker").is_ok() {
-        let mut c = Command::new("hyprpicker");
-        c.args(["-r", "-z"])
-            .stdout(Stdio::null())
-            .stderr(Stdio::null());
-        c.spawn().ok()
-    } else {
-        None
+    let strings_file = match &cli.lang {
+        Some(lang) => Some(config::config_dir().join(format!("lang/{lang}.toml"))),
+        None => cli.strings.as_deref().map(expand_path).transpose()?,
+    };
+    let strings = match strings_file {
+        Some(path) => Strings::load(&path)?,
+        None => Strings::default(),
     };
 
-    // map enums to grimblast args
-    let how_s = match how {
-        SaveHow::Copy => "copy",
-        SaveHow::Save => "save",
-        SaveHow::Copysave => "copysave",
-        SaveHow::Edit => "edit",
+    if cli.iso_names {
+        cli.name_template = cli.name_template.or_else(|| Some(ISO_TEMPLATE.into()));
+    }
+    let (name_template, format) = match cli.name_template.as_deref().map(split_template_ext) {
+        Some((stem, Some(ext))) => {
+            if let Some(fmt) = cli.format.filter(|&f| f != ext) {
+                bail!("name template ends in .{ext} but the format is {fmt}");
+            }
+            (Some(stem.to_string()), ext)
+        }
+        _ => (cli.name_template, cli.format.unwrap_or(Format::Png)),
     };
-    let kind_s = match kind {
-        CaptureKind::Screen => "screen",
-        CaptureKind::Output => "output",
-        CaptureKind::Area => "area",
+
+    let tools = Tools::from_env();
+    let opts = Options {
+        backend: backend::select(cli.backend.unwrap_or_default(), &tools, cli.grimblast_arg).into(),
+        tools,
+        shot_dir,
+        extra_dirs,
+        format,
+        name_template,
+        quality: cli.quality,
+        rofi_config: cli.rofi_config.as_deref().map(expand_path).transpose()?,
+        rofi_args: cli.rofi_arg,
+        utc: cli.utc,
+        overwrite: cli.overwrite,
+        save_mode: cli.save_mode.filter(|_| !cli.ask_save),
+        delay: cli.delay,
+        timer_presets: cli.timer_presets,
+        save_dirs: cli.save_dirs,
+        print_path: cli.print_path,
+        json: cli.json,
+        notify: !(cli.no_notify || cli.stdout),
+        notify_timeout: cli.notify_timeout,
+        notify_actions: cli.notify_actions,
+        retries: cli.retries,
+        sound: cli.sound,
+        copy_path: cli.copy_path,
+        freeze: cli.freeze,
+        no_freeze: cli.no_freeze,
+        cursor: cli.cursor,
+        unfreeze_delay_ms: cli.unfreeze_delay_ms,
+        output: cli.output,
+        overlay_countdown: cli.overlay_countdown,
+        countdown_interval: cli.countdown_interval.unwrap_or(5),
+        dry_run: cli.dry_run,
+        open: cli.open,
+        reveal: cli.reveal,
+        editor: cli.editor,
+        redact: cli.redact.then_some(cli.redact_style),
+        metadata: !cli.no_metadata && !cli.strip_metadata,
+        strip_metadata: cli.strip_metadata,
+        pick_after: cli.pick_after,
+        pin: cli.pin.then(|| cli.pin_viewer.unwrap_or_default()),
+        stdout: cli.stdout,
+        exec: cli.exec,
+        upload: cli.upload,
+        upload_url: cli.upload_url,
+        clipboard_cmd: cli.clipboard_cmd,
+        history_limit: cli.history_limit,
+        strings,
+        optimize: cli.optimize,
+        verify: cli.verify,
+        max_size: (cli.max_width.is_some() || cli.max_height.is_some()).then(|| {
+            (
+                cli.max_width.unwrap_or(u32::MAX),
+                cli.max_height.unwrap_or(u32::MAX),
+            )
+        }),
+        share: cli.share_copy.then(|| {
+            (
+                cli.share_size.unwrap_or(1280),
+                cli.share_quality.unwrap_or(75),
+            )
+        }),
+        stamp: cli.stamp,
+        selector: cli.selector.or_else(|| {
+            slurp_style(
+                cli.slurp_color.as_deref(),
+                cli.slurp_border,
+                cli.slurp_bg.as_deref(),
+            )
+        }),
+        shadow: cli.shadow.then_some(shadow::Shadow {
+            padding: cli.shadow_padding,
+            blur: cli.shadow_blur.max(0.0),
+            radius: cli.corner_radius,
+            color: cli.shadow_color,
+        }),
+        stamp_pos: cli.stamp_pos,
+        subdir: cli
+            .subdir_template
+            .or_else(|| cli.subdir_by_date.then(|| DEFAULT_SUBDIR.into())),
     };
 
-    // run grimblast
-    let status = Command::new("grimblast")
-        .args(["--notify", how_s, kind_s, &tmp_path.to_string_lossy()])
-        .status()
-        .context("running grimblast")?;
+    if cli.copy_last {
+        return copy_last(&opts);
+    }
 
-    // unfreeze screen if we stared hyprpicker
-    if let Some(mut child) = picker_child.take() {
-        let _ = child.kill();
+    if cli.record {
+        let kind = instant.map_or(CaptureKind::Screen, |(kind, _)| kind);
+        return record::toggle(kind, cli.delay, &opts);
+    }
+    if let Some(secs) = cli.gif {
+        let kind = instant.map_or(CaptureKind::Area, |(kind, _)| kind);
+        return record::gif(kind, secs, cli.delay, &opts);
     }
 
-    if !status.success() {
-        bail!("grimblast failed");
+    // the menu picks come later, and take checks those
+    let (kind, how) = instant.unwrap_or((CaptureKind::Screen, SaveHow::Save));
+    require_backend(&opts, kind, how)?;
+    if opts.notify {
+        tools::require(&opts.tools.notify_send)
+            .context("notifications need notify-send (or pass --no-notify)")?;
+    }
+    if let Some(cmd) = &opts.clipboard_cmd {
+        if cmd.trim().is_empty() {
+            bail!("--clipboard-cmd is empty");
+        }
+        require_clipboard(&opts).context("--clipboard-cmd needs an installed program")?;
+    }
+    if cli.copy_path {
+        require_clipboard(&opts).context("--copy-path needs wl-copy (or xclip on X11)")?;
+    }
+    if opts.share.is_some() {
+        if opts.format == Format::Webp {
+            bail!("--share-copy can't read webp screenshots back; save png or jpg");
+        }
+        require_clipboard(&opts).context("--share-copy copies the small image's path")?;
+    }
+    if opts.format == Format::Webp {
+        tools::require(Path::new("cwebp")).context("webp output needs cwebp (libwebp)")?;
     }
 
-    // move into screenshots dir if a file was written (copy mode may not write a file)
-    if tmp_path.exists() {
-        let dest = shot_dir.join(name);
-        fs::rename(&tmp_path, &dest).or_else(|_| {
-            fs::copy(&tmp_path, &dest)
-                .map(|_| {
-                    let _ = fs::remove_file(&tmp_path);
-                })
-                .ok();
-            Ok::<(), anyhow::Error>(())
-        })?;
-        notify("Screenshot saved", &format!("DIR: {}", shot_dir.display()))?;
+    if let Ok(slurp) = which("slurp") {
+        debug!("found slurp at {}", slurp.display());
     }
-    Ok(())
-}
 
-fn rofi_pick(prompt: &str, options: &[&str], cfg: Option<&Path>) -> Result<String> {
-    let mut cmd = Command::new("rofi");
-    cmd.args(["-dmenu", "-i", "-no-show-icons", "-p", prompt]);
-    if let Some(c) = cfg {
-        cmd.args(["-config", &c.to_string_lossy()]);
+    if let Some(name) = &opts.output {
+        // only a hint: hyprctl may be missing or the monitor may come back later
+        if let Ok(monitors) = hypr::monitors()
+            && !monitors.iter().any(|m| &m.name == name)
+        {
+            warn!("no monitor named {name} in `hyprctl monitors`");
+        }
     }
-    let input = options.join("\n");
-    let mut child = cmd
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .spawn()
-        .context("spawning rofi")?;
-    {
-        let mut stdin = child.stdin.take().unwrap();
-        stdin.write_all(input.as_bytes())?;
+
+    // reject a broken template before any UI pops up
+    let now = capture_time(opts.utc);
+    file_name(
+        opts.format,
+        opts.name_template.as_deref(),
+        &now,
+        CaptureKind::Screen,
+        1,
+    )?;
+    if let Some(text) = &opts.stamp {
+        expand_template(text, &now, CaptureKind::Screen, 1)?;
     }
-    let output = child.wait_with_output()?;
-    if !output.status.success() {
-        bail!("rofi cancceled");
+
+    if cli.all_outputs {
+        if let Some(secs) = cli.delay.filter(|_| !opts.dry_run) {
+            countdown(secs, &opts)?;
+        }
+        return take_all_outputs(&opts);
     }
-    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
-}
 
-fn countdown(mut secs: u64) -> Result<()> {
-    if secs > 10 {
-        notify("Taking screenshot", &format!("in {secs} seconds"))?;
-        sleep(Duration::from_secs(secs - 10));
-        secs = 10;
+    if let Some((kind, how)) = instant {
+        if let Some(secs) = cli.delay.filter(|_| !opts.dry_run) {
+            countdown(secs, &opts)?;
+        }
+        if cli.repeat {
+            // keep selecting until the user cancels
+            let mut round = 1;
+            loop {
+                match take(kind, how, &opts, Some(round)) {
+                    Ok(true) => round += 1,
+                    Ok(false) => return Ok(()),
+                    // cancelling is how a session ends; only the first one counts
+                    Err(e) if round > 1 && CaptureError::is_cancelled(&e) => {
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        return take(kind, how, &opts, None).map(drop);
     }
-    while secs > 0 {
-        notify("Taking screenshot", &format!("in {secs} seconds"))?;
-        sleep(Duration::from_secs(1));
-        secs -= 1;
+
+    // default to interactive if nothing else was specified
+    run_interactive(&opts)
+}
+
+/// Warnings only by default; `RUST_LOG` still overrides the level
+fn init_logging(verbose: u8) {
+    let level = match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    env_logger::Builder::new()
+        .filter_level(level)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+}
+
+fn print_versions(tools: &Tools) {
+    println!("{:<12} {}", "crabture", env!("CARGO_PKG_VERSION"));
+    // not every tool spells its version flag the same way
+    let probes: [(&Path, &str); 4] = [
+        (&tools.grimblast, "--version"),
+        (&tools.rofi, "-version"),
+        (Path::new("slurp"), "-v"),
+        (Path::new("hyprpicker"), "--version"),
+    ];
+    for (tool, flag) in probes {
+        let version = match which(tool) {
+            Ok(_) => tool_version(tool, flag).unwrap_or_else(|| "unknown".into()),
+            Err(_) => "not found".into(),
+        };
+        println!("{:<12} {version}", tool.display());
     }
-    Ok(())
 }
 
-fn notify(title: &str, body: &str) -> Result<()> {
-    let _ = Command::new("notify-send")
-        .args(["-t", "1000", title, body])
-        .status();
-    Ok(())
+/// First line `tool flag` prints on stdout or stderr, if it runs at all
+fn tool_version(tool: &Path, flag: &str) -> Option<String> {
+    let out = Command::new(tool)
+        .arg(flag)
+        .stdin(Stdio::null())
+        .output()
+        .ok()?;
+    let text = [&out.stdout, &out.stderr]
+        .into_iter()
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        .find(|s| !s.trim().is_empty())?;
+    text.lines().next().map(|l| l.trim().to_string())
 }
 
-fn file_name(fmt: &str) -> String {
-    let now = OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc());
-    let ts = format!(
-        "{:02}{:02}{:04}_{:02}{:02}{:02}",
-        now.day(),
-        now.month() as u8,
-        now.year(),
-        now.hour(),
-        now.minute(),
-        now.second()
-    );
-    let ext = if fmt.eq_ignore_ascii_case("jpg") || fmt.eq_ignore_ascii_case("jpeg") {
-        "jpg"
+fn list_outputs() -> Result<()> {
+    let monitors = if which("hyprctl").is_ok() {
+        hypr::monitors()?
+    } else if which("wlr-randr").is_ok() {
+        hypr::wlr_randr_monitors()?
     } else {
-        "png"
+        bail!("--list-outputs needs hyprctl (Hyprland) or wlr-randr in PATH");
     };
-    format!("screenshot_{ts}.{ext}")
+    println!("{:<16} {:<12} POSITION", "NAME", "RESOLUTION");
+    for m in monitors {
+        let res = format!("{}x{}", m.width, m.height);
+        println!("{:<16} {:<12} {},{}", m.name, res, m.x, m.y);
+    }
+    Ok(())
 }
 
-fn home() -> PathBuf {
-    PathBuf::from(env::var("HOME").unwrap_or_else(|_| ".".into()))
+/// Put the most recent screenshot (from history, else the newest image in
+/// the screenshot dir) back on the clipboard
+fn copy_last(opts: &Options) -> Result<()> {
+    require_clipboard(opts).context("--copy-last needs wl-copy (or xclip on X11)")?;
+    let last = history::read()?
+        .into_iter()
+        .rev()
+        .map(|e| e.path)
+        .find(|p| p.is_file())
+        .or_else(|| newest_image(&opts.shot_dir));
+    let Some(path) = last else {
+        notify(
+            opts,
+            None,
+            "No screenshot to copy",
+            &format!("DIR: {}", opts.shot_dir.display()),
+            None,
+        )?;
+        return Ok(());
+    };
+    debug!("copying {}", path.display());
+    copy_image(opts, &path, mime_type(&path))?;
+    notify(
+        opts,
+        None,
+        "Screenshot copied",
+        &path.file_name().unwrap_or_default().to_string_lossy(),
+        None,
+    )?;
+    Ok(())
 }
 
-fn xdg_screenshots_dir() -> Option<PathBuf> {
-    if let Ok(path) = env::var("XDG_SCREENSHOTS_DIR") {
-        return Some(PathBuf::from(
-            path.replace("$HOME", &home().to_string_lossy()),
-        ));
-    }
-    let path = home().join(".config/user-dirs.dirs");
-    if let Ok(s) = fs::read_to_string(path) {
-        for line in s.lines() {
-            if let Some(rest) = line.strip_prefix("XDG_SCREENSHOTS_DIR=") {
-                let raw = rest.trim().trim_matches('"').to_string();
-                return Some(PathBuf::from(
-                    raw.replace("$HOME", &home().to_string_lossy()),
-                ));
+fn newest_image(dir: &Path) -> Option<PathBuf> {
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .filter(|e| Format::from_path(&e.path()).is_some())
+        // skip a capture still in flight
+        .filter(|e| {
+            !e.file_name()
+                .to_string_lossy()
+                .starts_with(".crabture-tmp-")
+        })
+        .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+        .max_by_key(|(modified, _)| *modified)
+        .map(|(_, path)| path)
+}
+
+fn run_interactive(opts: &Options) -> Result<()> {
+    tools::require(&opts.tools.rofi)?;
+    let t = &opts.strings;
+    let delay = match opts.delay {
+        Some(secs) => secs,
+        None => {
+            let when = rofi_pick(
+                opts,
+                &t.when_prompt,
+                &[&t.immediate, &t.delayed, &t.area_edit],
+            )?;
+            // shortcut for the most common flow: straight to the selection and editor
+            if when == t.area_edit {
+                let opts = in_save_dir(opts, SaveHow::Edit)?;
+                return take(CaptureKind::Area, SaveHow::Edit, &opts, None).map(drop);
+            }
+            if when == t.delayed {
+                let mut presets = timer_presets(&opts.timer_presets);
+                presets.push(&t.custom_timer);
+                let timer = rofi_pick(opts, &t.timer_prompt, &presets)?;
+                if timer == t.custom_timer {
+                    custom_delay(opts)?
+                } else {
+                    parse_timer(&timer).unwrap_or(5)
+                }
+            } else {
+                0
             }
         }
-    }
-    None
-}
+    };
+
+    // last run's picks go first so rofi preselects them
+    let last = LastChoice::load();
 
-fn ensure_tools(names: &[&str]) -> Result<()> {
-    for n in names {
-        if which(n).is_err() {
-            bail!("required tool not found in PATH: {}", n);
+    let mut kinds = [
+        (t.capture_screen.as_str(), CaptureKind::Screen),
+        (t.capture_output.as_str(), CaptureKind::Output),
+        (t.capture_area.as_str(), CaptureKind::Area),
+        (t.capture_window.as_str(), CaptureKind::ActiveWindow),
+    ];
+    promote(&mut kinds, |k| last.kind.as_deref() == Some(k.as_str()));
+    let kind = rofi_choose(opts, &t.kind_prompt, &kinds, CaptureKind::Area)?;
+
+    let how = match opts.save_mode {
+        Some(how) => how,
+        None => {
+            let mut hows = [
+                (t.copy.as_str(), SaveHow::Copy),
+                (t.save.as_str(), SaveHow::Save),
+                (t.copy_save.as_str(), SaveHow::Copysave),
+                (t.edit.as_str(), SaveHow::Edit),
+            ];
+            promote(&mut hows, |h| last.mode.as_deref() == Some(h.as_str()));
+            rofi_choose(opts, &t.save_prompt, &hows, SaveHow::Edit)?
         }
+    };
+
+    let picked = LastChoice {
+        kind: Some(kind.as_str().into()),
+        // a configured mode wasn't a pick, so keep the last real one for --ask-save
+        mode: match opts.save_mode {
+            Some(_) => last.mode.clone(),
+            None => Some(how.as_str().into()),
+        },
+    };
+    if !opts.dry_run
+        && let Err(e) = picked.store()
+    {
+        warn!("could not remember menu choices: {e:#}");
+    }
+
+    let opts = in_save_dir(opts, how)?;
+
+    if delay > 0 && !opts.dry_run {
+        countdown(delay, &opts)?;
+    }
+
+    take(kind, how, &opts, None).map(drop)
+}
+
+/// `opts` pointed at the configured save dir this capture goes to
+fn in_save_dir(opts: &Options, how: SaveHow) -> Result<Cow<'_, Options>> {
+    Ok(match pick_save_dir(opts, how)? {
+        Some(dir) => Cow::Owned(Options {
+            shot_dir: dir,
+            ..opts.clone()
+        }),
+        None => Cow::Borrowed(opts),
+    })
+}
+
+/// Which of the configured save dirs this capture goes to, asking only when
+/// there are several; `None` leaves the usual dir
+fn pick_save_dir(opts: &Options, how: SaveHow) -> Result<Option<PathBuf>> {
+    let dir = match opts.save_dirs.as_slice() {
+        // a plain copy never reaches the disk, so there is nothing to place
+        _ if how == SaveHow::Copy => return Ok(None),
+        [] => return Ok(None),
+        [only] => only,
+        dirs => {
+            let labels: Vec<String> = dirs.iter().map(|d| d.display().to_string()).collect();
+            let labels: Vec<&str> = labels.iter().map(String::as_str).collect();
+            let picked = rofi_pick(opts, &opts.strings.dir_prompt, &labels)?;
+            match dirs.iter().find(|d| d.display().to_string() == picked) {
+                Some(dir) => dir,
+                // typed something that isn't on the list; keep the usual dir
+                None => return Ok(None),
+            }
+        }
+    };
+    let dir = expand_path(dir)?;
+    ensure_writable(&dir, opts.dry_run)?;
+    debug!("screenshot dir: {}", dir.display());
+    Ok(Some(dir))
+}
+
+const DEFAULT_TIMERS: [&str; 5] = ["5s", "10s", "20s", "30s", "60s"];
+
+/// A `<n>s` timer label as seconds
+fn parse_timer(label: &str) -> Option<u64> {
+    label
+        .trim()
+        .strip_suffix('s')?
+        .parse()
+        .ok()
+        .filter(|&secs| secs <= MAX_DELAY)
+}
+
+/// Ask for a number of seconds as free text, again until it parses; Escape cancels
+fn custom_delay(opts: &Options) -> Result<u64> {
+    loop {
+        // no entries, so whatever is typed comes back as the pick
+        let typed = rofi_pick(opts, &opts.strings.custom_timer_prompt, &[])?;
+        let secs = typed.parse().ok().filter(|&s| s <= MAX_DELAY);
+        match secs.or_else(|| parse_timer(&typed)) {
+            Some(secs) => return Ok(secs),
+            None => warn!("not a number of seconds: {typed:?}"),
+        }
+    }
+}
+
+/// The configured timer labels if every one parses, else the defaults
+fn timer_presets(configured: &[String]) -> Vec<&str> {
+    if configured.is_empty() {
+        return DEFAULT_TIMERS.to_vec();
+    }
+    if let Some(bad) = configured.iter().find(|p| parse_timer(p).is_none()) {
+        warn!("ignoring timer presets: {bad:?} is not like \"10s\"");
+        return DEFAULT_TIMERS.to_vec();
+    }
+    configured.iter().map(String::as_str).collect()
+}
+
+const DEFAULT_SUBDIR: &str = "{year}-{month}";
+
+/// Capture once. `round` numbers the shots of a `--repeat` session. Returns
+/// false under `--dry-run`; a cancelled selection is a
+/// [`CaptureError::Cancelled`] error
+fn take(kind: CaptureKind, how: SaveHow, opts: &Options, round: Option<u32>) -> Result<bool> {
+    let backend = &*opts.backend;
+    require_backend(opts, kind, how)?;
+    let kind = backend.resolve(kind);
+    let pick_after = opts.pick_after && kind == CaptureKind::Area;
+    if pick_after && !backend.wayland() {
+        bail!("--pick-after selects with slurp, which needs Wayland");
+    }
+    // what the backend is asked for; --pick-after crops the area out afterwards
+    let shot_kind = if pick_after {
+        CaptureKind::Screen
+    } else {
+        kind
+    };
+    // one clock reading for the subfolder and the file name
+    let now = capture_time(opts.utc);
+    let sub = match &opts.subdir {
+        Some(t) => {
+            let sub = PathBuf::from(expand_template(t, &now, kind, 1)?);
+            if sub.is_absolute() || sub.components().any(|c| c.as_os_str() == "..") {
+                bail!(
+                    "subfolder must stay inside the screenshot dir: {}",
+                    sub.display()
+                );
+            }
+            Some(sub)
+        }
+        None => None,
+    };
+    let dirs: Vec<PathBuf> = std::iter::once(&opts.shot_dir)
+        .chain(&opts.extra_dirs)
+        .map(|d| sub.as_ref().map_or_else(|| d.clone(), |s| d.join(s)))
+        .collect();
+    if !opts.dry_run {
+        for dir in &dirs {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+    }
+    let (shot_dir, extra_dirs) = (dirs[0].as_path(), &dirs[1..]);
+    let format = opts.format;
+    let template = opts.name_template.as_deref();
+    let quality = opts.quality;
+    if quality.is_some() && format == Format::Png {
+        bail!("--quality only applies to jpg or webp output, not png");
+    }
+
+    // bump {seq} until the name is free; templates without it are used as-is,
+    // except that --repeat shots get their number appended
+    let uses_seq = template.is_some_and(|t| t.contains("{seq}"));
+    let mut seq = round.unwrap_or(1);
+    let name = loop {
+        let mut name = file_name(format, template, &now, kind, seq)?;
+        if let Some(n) = round.filter(|_| !uses_seq) {
+            name = format!(
+                "{}_{n}.{}",
+                name.trim_end_matches(&format!(".{}", format.ext())),
+                format.ext()
+            );
+        }
+        if !uses_seq || !shot_dir.join(&name).exists() {
+            break name;
+        }
+        seq += 1;
+    };
+    // timestamps only have second resolution, so back-to-back shots can collide
+    let name = if opts.overwrite {
+        name
+    } else {
+        unclobbered_name(&name, |n| dirs.iter().any(|d| d.join(n).exists()))
+    };
+    // write next to the destination so the final move is a same-filesystem rename;
+    // the guards remove whatever is left on every way out of here
+    let stem = name.trim_end_matches(&format!(".{}", format.ext()));
+    let (tmp_path, tmp_guard) = temp_file(shot_dir, stem, format.ext(), opts.dry_run)?;
+    // backends only write png; webp is converted from it afterwards
+    let (capture_path, capture_guard) = match format {
+        Format::Webp => temp_file(shot_dir, stem, "png", opts.dry_run)?,
+        _ => (tmp_path.clone(), None),
+    };
+    let _cleanup = (tmp_guard, capture_guard);
+
+    // with a custom editor grimblast only saves and we launch the editor ourselves
+    let editor = opts
+        .editor
+        .as_deref()
+        .filter(|_| matches!(how, SaveHow::Edit));
+    if how == SaveHow::Edit && editor.is_none() && !backend.handles_actions() {
+        bail!(
+            "{} has no editor of its own, set one with --editor",
+            backend.name()
+        );
+    }
+    // steps that rework the capture must run before anyone sees it, so the
+    // backend only saves and the editor, if any, comes after them
+    let post_capture = pick_after || opts.redact.is_some();
+    if how == SaveHow::Edit && editor.is_none() && post_capture {
+        bail!("editing after --pick-after or --redact needs an editor set with --editor");
+    }
+    // grimblast only copies its own png, so converted or post-processed captures are
+    // copied by us afterwards, as is everything from backends that can't copy
+    let copy_converted = how.copies()
+        && (!backend.handles_actions()
+            || format != Format::Png
+            || opts.max_size.is_some()
+            || opts.redact.is_some()
+            || opts.clipboard_cmd.is_some()
+            || opts.strip_metadata
+            || pick_after
+            || opts.shadow.is_some());
+    let action = match how {
+        _ if editor.is_some() || copy_converted || post_capture => "save",
+        // --stdout needs a file to stream from
+        SaveHow::Copy if opts.stdout => "copysave",
+        _ => how.as_str(),
+    };
+
+    let mut capture = backend.command(&backend::Request {
+        kind: shot_kind,
+        action,
+        dest: &capture_path,
+        notify: opts.notify,
+        cursor: opts.cursor,
+        output: opts.output.as_deref(),
+    })?;
+    // stdout only echoes the path and would pollute --json/--print-path; keep
+    // stderr for error reporting (--notify goes through notify-send either way)
+    capture.stdout(Stdio::null()).stderr(Stdio::piped());
+
+    debug!("temp file: {}", capture_path.display());
+    if opts.dry_run {
+        println!("{}", command_line(&capture));
+        return Ok(false);
+    }
+
+    let focus = backend.prepare(shot_kind, opts.output.as_deref())?;
+
+    // grimblast has no selector option, so shadow `slurp` on its PATH instead;
+    // --redact goes through it too, to learn where the area was
+    let selector = opts.selector.as_deref().or(opts.redact.map(|_| "slurp"));
+    let selector = match (selector, shot_kind) {
+        (Some(cmd), CaptureKind::Area) if backend.wayland() => {
+            Some(SelectorShim::install(&mut capture, cmd)?)
+        }
+        _ => None,
+    };
+
+    // freeze screen for area selection (or always with --freeze) if hyprpicker
+    // exists; let grimblast run slurp
+    let use_freeze = match shot_kind {
+        _ if opts.no_freeze || !backend.wayland() => false,
+        _ if opts.freeze => true,
+        CaptureKind::Area if hypr::mixed_scaling() => {
+            debug!("mixed monitor scaling, not freezing for area selection");
+            false
+        }
+        CaptureKind::Area => true,
+        _ => false,
+    };
+
+    let mut attempt = 0;
+    let output = loop {
+        let freeze = if use_freeze { Freeze::start() } else { None };
+
+        debug!("running {}", command_line(&capture));
+        let output = capture
+            .output()
+            .with_context(|| format!("running {}", backend.name()));
+
+        // unfreeze screen if we stared hyprpicker, giving slow machines a moment
+        // to finish the frame grab first; the guard kills it regardless
+        if freeze.is_some() && opts.unfreeze_delay_ms > 0 {
+            sleep(Duration::from_millis(opts.unfreeze_delay_ms));
+        }
+        drop(freeze);
+
+        let output = output?;
+        if output.status.success()
+            || backend.cancelled(shot_kind, &output)
+            || attempt >= opts.retries
+        {
+            break output;
+        }
+        attempt += 1;
+        debug!(
+            "{} failed ({}), retry {attempt}",
+            backend.name(),
+            output.status
+        );
+        sleep(Duration::from_millis(250 * u64::from(attempt)));
+    };
+    drop(focus);
+    let taken_at = capture_time(opts.utc);
+
+    if backend.cancelled(shot_kind, &output) {
+        return Err(CaptureError::Cancelled).context("area selection");
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    if !output.status.success() {
+        if stderr.is_empty() {
+            bail!("{} failed ({})", backend.name(), output.status);
+        }
+        bail!("{} failed ({}): {stderr}", backend.name(), output.status);
+    }
+    if !stderr.is_empty() {
+        eprintln!("{stderr}");
+    }
+    // an empty slurp selection can still exit 0, leaving only the empty temp
+    // file made for the capture; that's a cancel, not a broken screenshot
+    if action != "copy" && fs::metadata(&capture_path).is_ok_and(|m| m.len() == 0) {
+        let _ = fs::remove_file(&capture_path);
+        notify(opts, None, "Selection cancelled", "Nothing was saved", None)?;
+        return Err(CaptureError::Cancelled).context("empty capture");
+    }
+    // grimblast puts a plain copy on the clipboard itself, but some versions
+    // still write the file too; nothing below needs it
+    if action == "copy" {
+        let _ = fs::remove_file(&capture_path);
+    }
+    if let Some(sound) = &opts.sound {
+        play_sound(sound);
+    }
+    let mut area = selector.as_ref().and_then(SelectorShim::geometry);
+    if pick_after && has_data(&capture_path) {
+        area = Some(pick_after_capture(opts, &capture_path)?);
+    }
+    if let Some(style) = opts.redact.filter(|_| has_data(&capture_path)) {
+        redact_capture(opts, kind, &capture_path, area.as_deref(), style)?;
+    }
+    if opts.verify
+        && has_data(&capture_path)
+        && let Err(e) = format::verify(&capture_path)
+    {
+        let _ = fs::remove_file(&capture_path);
+        return Err(e).context(format!(
+            "{} wrote a corrupt capture, not saving it",
+            backend.name()
+        ));
+    }
+    if let Some((w, h)) = opts.max_size.filter(|_| has_data(&capture_path)) {
+        format::fit_within(&capture_path, w, h)?;
+    }
+    if let Some(editor) = editor {
+        run_editor(editor, &capture_path)?;
+    }
+    // copy-only shots never reach the disk, so there is nothing to stamp
+    if let Some(text) = opts
+        .stamp
+        .as_deref()
+        .filter(|_| !matches!(how, SaveHow::Copy) && has_data(&capture_path))
+    {
+        let label = expand_template(text, &taken_at, kind, seq)?;
+        stamp::stamp(&capture_path, &label, opts.stamp_pos)?;
+    }
+    if let Some(shadow) = &opts.shadow
+        && has_data(&capture_path)
+    {
+        match kind {
+            CaptureKind::ActiveWindow | CaptureKind::Output => {
+                if format == Format::Jpg {
+                    warn!("jpg has no transparency, the shadow sits on black");
+                }
+                shadow::apply(&capture_path, shadow)?;
+            }
+            _ => debug!("--shadow only frames window and output captures"),
+        }
+    }
+    if format == Format::Webp && has_data(&capture_path) {
+        let converted = format::png_to_webp(&capture_path, &tmp_path, quality);
+        let _ = fs::remove_file(&capture_path);
+        converted?;
+    }
+
+    if opts.stdout && !has_data(&tmp_path) {
+        bail!(
+            "{} did not write a file to stream to stdout",
+            backend.name()
+        );
+    }
+
+    // move into screenshots dir if a file was written (copy mode may not write a file)
+    let mut saved = None;
+    let mut copies = Vec::new();
+    let mut pending_action = None;
+    if has_data(&tmp_path) {
+        if format == Format::Jpg {
+            match quality {
+                Some(q) => format::reencode_jpeg(&tmp_path, q)?,
+                // the clipboard gets exactly these bytes, so they must really be jpeg;
+                // the encoder writes nothing but the pixels, which also strips metadata
+                None if copy_converted || opts.strip_metadata => {
+                    format::reencode_jpeg(&tmp_path, 90)?
+                }
+                None => {}
+            }
+        }
+        if opts.strip_metadata && format == Format::Png {
+            format::strip_png(&tmp_path)?;
+        }
+        if opts.optimize && format == Format::Png {
+            format::optimize_png(&tmp_path);
+        }
+        if copy_converted {
+            copy_image(opts, &tmp_path, format.mime())?;
+        }
+        if opts.stdout {
+            let bytes = fs::read(&tmp_path).context("reading capture");
+            let _ = fs::remove_file(&tmp_path);
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(&bytes?)?;
+            stdout.flush()?;
+            return Ok(true);
+        }
+        // a plain copy only wrote the file to convert it
+        if how == SaveHow::Copy {
+            let _ = fs::remove_file(&tmp_path);
+        }
+        if !matches!(how, SaveHow::Copy) {
+            if opts.metadata {
+                let info = capture_info(opts, kind, taken_at, area.clone());
+                if let Err(e) = metadata::embed(&tmp_path, format, &info) {
+                    warn!("could not add metadata: {e:#}");
+                }
+            }
+            let dest = shot_dir.join(&name);
+            debug!("moving {} to {}", tmp_path.display(), dest.display());
+            move_file(&tmp_path, &dest)?;
+            if !dest.exists() {
+                bail!("{} vanished right after saving", dest.display());
+            }
+            let mut saved_dirs = vec![shot_dir.display().to_string()];
+            for dir in extra_dirs {
+                let extra = dir.join(&name);
+                if extra.exists() && !opts.overwrite {
+                    warn!("{} already exists, not overwriting it", extra.display());
+                    continue;
+                }
+                copy_file(&dest, &extra)?;
+                saved_dirs.push(dir.display().to_string());
+                copies.push(std::path::absolute(&extra)?);
+            }
+            let path = std::path::absolute(&dest)?;
+            let body = format!("DIR: {}", saved_dirs.join(", "));
+            if opts.notify_actions {
+                pending_action = notify_with_actions(opts, "Screenshot saved", &body, &path);
+            } else {
+                notify(opts, None, "Screenshot saved", &body, Some(&path))?;
+            }
+            let entry = history::Entry {
+                timestamp: taken_at.format(&Rfc3339)?,
+                kind: kind.as_str().into(),
+                mode: how.as_str().into(),
+                path: path.clone(),
+            };
+            if let Err(e) = history::append(&entry, opts.history_limit) {
+                warn!("could not update history: {e:#}");
+            }
+            saved = Some(path);
+        }
+    }
+
+    if let Some(path) = saved.as_ref().filter(|_| opts.copy_path) {
+        copy_text(opts, &path.to_string_lossy())?;
+    }
+
+    if let Some((path, (size, quality))) = saved.as_ref().zip(opts.share) {
+        let shared = share_copy(path, size, quality)?;
+        copy_text(opts, &shared.to_string_lossy())?;
+        notify(
+            opts,
+            None,
+            "Share copy ready",
+            &format!("Path copied: {}", shared.display()),
+            None,
+        )?;
+    }
+
+    if let Some(path) = saved.as_ref().filter(|_| opts.open) {
+        xdg_open(path);
+    }
+
+    if let Some(path) = saved.as_ref().filter(|_| opts.reveal) {
+        reveal(path);
+    }
+
+    if let Some(viewer) = opts.pin.as_deref() {
+        match saved.as_ref() {
+            // the screenshot is saved either way, so a missing viewer only warns
+            Some(path) if matches!(how, SaveHow::Save | SaveHow::Copysave) => {
+                if let Err(e) = pin(viewer, path) {
+                    warn!("{e:#}");
+                }
+            }
+            _ => debug!("--pin only shows saved screenshots"),
+        }
+    }
+
+    let hook_status = match saved.as_ref().zip(opts.exec.as_deref()) {
+        Some((path, cmd)) => Some(run_hook(cmd, path)?),
+        None => None,
+    };
+
+    let url = match saved.as_ref().filter(|_| opts.upload) {
+        Some(path) => {
+            let endpoint = opts.upload_url.as_deref().unwrap_or(upload::DEFAULT_URL);
+            let url = upload::upload(endpoint, path)?;
+            match require_clipboard(opts) {
+                Ok(()) => copy_text(opts, &url)?,
+                Err(e) => warn!("{e:#}, upload URL not copied"),
+            }
+            notify(opts, None, "Screenshot uploaded", &url, None)?;
+            Some(url)
+        }
+        None => None,
+    };
+
+    if opts.json {
+        let report = Report {
+            paths: saved.iter().cloned().chain(copies).collect(),
+            path: saved.clone(),
+            kind: kind.as_str(),
+            mode: how.as_str(),
+            format: format.ext(),
+            timestamp: taken_at.format(&Rfc3339)?,
+            clipboard: how.copies(),
+            exec_status: hook_status.and_then(|s| s.code()),
+            url,
+        };
+        println!("{}", serde_json::to_string(&report)?);
+    } else if let Some(path) = saved.as_ref().filter(|_| opts.print_path) {
+        println!("{}", path.display());
+    }
+
+    if let Some((child, path)) = pending_action.zip(saved) {
+        await_action(opts, child, &path, format.mime())?;
+    }
+    Ok(true)
+}
+
+/// `--all-outputs`: one capture per monitor, with the output name appended to
+/// the file name and a single summary notification at the end
+fn take_all_outputs(opts: &Options) -> Result<()> {
+    let monitors = hypr::monitors().context("--all-outputs lists monitors with hyprctl")?;
+    if monitors.is_empty() {
+        bail!("hyprctl reported no monitors");
+    }
+    let base = opts.name_template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    // hand focus back to where it was before we hopped across outputs, even
+    // if one of them fails
+    let _focus = monitors
+        .iter()
+        .find(|m| m.focused && !opts.dry_run)
+        .map(|m| hypr::RestoreFocus(m.name.clone()));
+    let mut written = 0;
+    for monitor in &monitors {
+        let per_output = Options {
+            output: Some(monitor.name.clone()),
+            name_template: Some(format!("{base}_{}", monitor.name)),
+            notify: false,
+            ..opts.clone()
+        };
+        if take(CaptureKind::Output, SaveHow::Save, &per_output, None)? {
+            written += 1;
+        }
+    }
+    if opts.dry_run {
+        return Ok(());
+    }
+    notify(
+        opts,
+        None,
+        "Screenshots saved",
+        &format!("{written} files in {}", opts.shot_dir.display()),
+        None,
+    )?;
+    Ok(())
+}
+
+/// Run the user's editor on the capture and wait for it. Editors are expected
+/// to write back to that same path (e.g. `swappy -f {} -o {}`)
+fn run_editor(cmdline: &str, path: &Path) -> Result<()> {
+    let (program, mut cmd) = path_command(cmdline, path).context("--editor is empty")?;
+    debug!("running {}", command_line(&cmd));
+    let status = cmd
+        .status()
+        .with_context(|| format!("running editor {program}"))?;
+    if !status.success() {
+        bail!("editor {program} failed ({status})");
+    }
+    Ok(())
+}
+
+/// `--pin`: open the screenshot in `viewer` (imv or feh when empty) and leave
+/// it running
+fn pin(viewer: &str, path: &Path) -> Result<()> {
+    let viewer = match viewer {
+        "" => ["imv", "feh"]
+            .into_iter()
+            .find(|v| which(v).is_ok())
+            .context("--pin needs imv or feh, or a viewer set with --pin-viewer")?,
+        v => v,
+    };
+    let (program, mut cmd) = path_command(viewer, path).context("--pin-viewer is empty")?;
+    debug!("running {}", command_line(&cmd));
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("running viewer {program}"))?;
+    Ok(())
+}
+
+/// `cmdline` split on whitespace with `{}` replaced by `path`, or `path`
+/// appended if there is no `{}`; `None` when `cmdline` is blank
+fn path_command<'a>(cmdline: &'a str, path: &Path) -> Option<(&'a str, Command)> {
+    let path_s = path.to_string_lossy();
+    let mut words = cmdline.split_whitespace();
+    let program = words.next()?;
+    let mut cmd = Command::new(program);
+    let mut substituted = false;
+    for word in words {
+        if word.contains("{}") {
+            substituted = true;
+            cmd.arg(word.replace("{}", &path_s));
+        } else {
+            cmd.arg(word);
+        }
+    }
+    if !substituted {
+        cmd.arg(path);
+    }
+    Some((program, cmd))
+}
+
+/// Render a command the way you'd type it in a shell
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|arg| shell_quote(&arg.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn shell_quote(arg: &str) -> String {
+    let plain = arg
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+    if plain && !arg.is_empty() {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Run the `--exec` hook through `sh -c` with `{}` replaced by the quoted path
+fn run_hook(cmdline: &str, path: &Path) -> Result<ExitStatus> {
+    let script = cmdline.replace("{}", &shell_quote(&path.to_string_lossy()));
+    debug!("running hook: {script}");
+    let status = Command::new("sh")
+        .args(["-c", &script])
+        .status()
+        .context("running --exec hook")?;
+    if !status.success() {
+        warn!("--exec hook failed ({status})");
+    }
+    Ok(status)
+}
+
+/// Running `hyprpicker` that freezes the screen until dropped
+struct Freeze(Child);
+
+impl Freeze {
+    fn start() -> Option<Self> {
+        which("hyprpicker").ok()?;
+        Command::new("hyprpicker")
+            .args(["-r", "-z"])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()
+            .map(Self)
+    }
+}
+
+impl Drop for Freeze {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn move_file(src: &Path, dest: &Path) -> Result<()> {
+    place_file_with(
+        src,
+        dest,
+        false,
+        |a, b| fs::hard_link(a, b),
+        |a, b| fs::rename(a, b),
+    )
+}
+
+/// Like [`move_file`] but keeps `src`; a hardlinked copy shares its bytes
+fn copy_file(src: &Path, dest: &Path) -> Result<()> {
+    place_file_with(
+        src,
+        dest,
+        true,
+        |a, b| fs::hard_link(a, b),
+        |a, b| fs::rename(a, b),
+    )
+}
+
+/// Put `src` at `dest` as cheaply as possible: hardlink, then rename (unless
+/// `keep_src`), then a full copy. Fails only if none of them worked
+fn place_file_with(
+    src: &Path,
+    dest: &Path,
+    keep_src: bool,
+    link: impl FnOnce(&Path, &Path) -> std::io::Result<()>,
+    rename: impl FnOnce(&Path, &Path) -> std::io::Result<()>,
+) -> Result<()> {
+    let remove_src = || {
+        if let Err(e) = fs::remove_file(src) {
+            warn!("could not remove {}: {e}", src.display());
+        }
+    };
+    match link(src, dest) {
+        Ok(()) => {
+            if !keep_src {
+                remove_src();
+            }
+            return Ok(());
+        }
+        Err(e) => debug!("hardlink to {} failed ({e})", dest.display()),
+    }
+    if !keep_src {
+        match rename(src, dest) {
+            Ok(()) => return Ok(()),
+            Err(e) => debug!("rename failed ({e}), copying instead"),
+        }
+    }
+    fs::copy(src, dest).with_context(|| format!("saving {}", dest.display()))?;
+    if !keep_src {
+        remove_src();
+    }
+    Ok(())
+}
+
+/// `name` if `taken` says it's free, else the first free `stem-1.ext`,
+/// `stem-2.ext`, ...
+fn unclobbered_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+    let (stem, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let mut n = 1;
+    loop {
+        let candidate = if ext.is_empty() {
+            format!("{stem}-{n}")
+        } else {
+            format!("{stem}-{n}.{ext}")
+        };
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// A styled slurp command line for the selector shim, or `None` to leave slurp alone
+fn slurp_style(color: Option<&str>, border: Option<u32>, bg: Option<&str>) -> Option<String> {
+    if color.is_none() && border.is_none() && bg.is_none() {
+        return None;
+    }
+    let mut cmd = String::from("slurp");
+    if let Some(color) = color {
+        cmd.push_str(&format!(" -c {}", shell_quote(color)));
+    }
+    if let Some(border) = border {
+        cmd.push_str(&format!(" -w {border}"));
+    }
+    if let Some(bg) = bg {
+        cmd.push_str(&format!(" -b {}", shell_quote(bg)));
+    }
+    Some(cmd)
+}
+
+/// A slurp geometry, through `--selector` or the slurp styling when set, the
+/// same way area screenshots get theirs. Escape is a
+/// [`CaptureError::Cancelled`] error
+fn select_area(opts: &Options) -> Result<String> {
+    let mut cmd = Command::new("slurp");
+    let _selector = match &opts.selector {
+        Some(sel) => Some(SelectorShim::install(&mut cmd, sel)?),
+        None => None,
+    };
+    let out = cmd
+        .stderr(Stdio::piped())
+        .output()
+        .context("running slurp")?;
+    let geometry = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    match out.status.code() {
+        Some(0) if !geometry.is_empty() => Ok(geometry),
+        Some(0 | 1) => Err(CaptureError::Cancelled).context("area selection"),
+        _ => bail!("slurp failed ({})", out.status),
+    }
+}
+
+/// Write a jpg of the screenshot at `path`, no larger than `size` on either
+/// side, into the `shared` dir beside it
+fn share_copy(path: &Path, size: u32, quality: u8) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(".")).join("shared");
+    fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+    let name =
+        Path::new(path.file_name().context("saved path has no file name")?).with_extension("jpg");
+    let dest = dir.join(name);
+    // encoded straight from the original, which must stay as it is
+    let img = format::load(path, "the share copy")?;
+    let small = format::shrink(&img, size, size);
+    format::save_jpeg(&dest, small.as_ref().unwrap_or(&img), quality)?;
+    debug!("shared copy at {}", dest.display());
+    Ok(dest)
+}
+
+/// What goes into the saved file's metadata; lookups that fail are left out
+fn capture_info(
+    opts: &Options,
+    kind: CaptureKind,
+    taken: OffsetDateTime,
+    area: Option<String>,
+) -> metadata::Info {
+    let output = match kind {
+        CaptureKind::Output => opts.output.clone().or_else(|| {
+            hypr::monitors()
+                .ok()?
+                .into_iter()
+                .find(|m| m.focused)
+                .map(|m| m.name)
+        }),
+        _ => None,
+    };
+    let geometry = match kind {
+        CaptureKind::Area => area,
+        CaptureKind::ActiveWindow => hypr::active_window()
+            .ok()
+            .flatten()
+            .map(|w| format!("{},{} {}x{}", w.at.0, w.at.1, w.size.0, w.size.1)),
+        _ => None,
+    };
+    let mut software = format!("crabture {}", env!("CARGO_PKG_VERSION"));
+    if let Some(version) = opts.backend.version() {
+        software.push_str(&format!(", {version}"));
+    }
+    metadata::Info {
+        taken,
+        kind: kind.as_str(),
+        output,
+        geometry,
+        software,
+    }
+}
+
+/// `--pick-after`: crop the full-screen capture at `path` to an area picked
+/// over the frozen screen, returning that area's geometry
+fn pick_after_capture(opts: &Options, path: &Path) -> Result<String> {
+    let captured = redact::captured(CaptureKind::Screen, None, None)?;
+    let freeze = if opts.no_freeze {
+        None
+    } else {
+        Freeze::start()
+    };
+    let geometry = select_area(opts);
+    drop(freeze);
+    let geometry = geometry?;
+    let rect = redact::Rect::parse(&geometry)
+        .with_context(|| format!("slurp printed an unexpected geometry: {geometry}"))?;
+    redact::crop(path, captured, rect)?;
+    Ok(geometry)
+}
+
+/// `--redact`: let the user pick rectangles over the frozen screen and hide
+/// them in the capture at `path`
+fn redact_capture(
+    opts: &Options,
+    kind: CaptureKind,
+    path: &Path,
+    area: Option<&str>,
+    style: redact::Style,
+) -> Result<()> {
+    if !opts.backend.wayland() {
+        bail!("--redact selects with slurp, which needs Wayland");
+    }
+    tools::require(Path::new("slurp")).context("--redact needs slurp")?;
+    let captured = redact::captured(kind, opts.output.as_deref(), area)?;
+    let freeze = if opts.no_freeze {
+        None
+    } else {
+        Freeze::start()
+    };
+    let rects = redact::select();
+    drop(freeze);
+    let rects = rects?;
+    if rects.is_empty() {
+        debug!("nothing selected to redact");
+        return Ok(());
+    }
+    redact::apply(path, captured, &rects, style)
+}
+
+/// A private temp dir holding a `slurp` script that runs the `--selector`
+/// command with grimblast's arguments and stdin, noting the geometry it
+/// printed; removed when dropped
+struct SelectorShim(tempfile::TempDir);
+
+impl SelectorShim {
+    /// `path` is the PATH to restore inside the script, so a selector that is
+    /// itself a `slurp` wrapper finds the real one rather than the shim
+    fn new(cmd: &str, path: &str) -> Result<Self> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // a fresh 0700 dir, so nobody else can plant the script or its output
+        let dir = tempfile::Builder::new()
+            .prefix("crabture-selector-")
+            .tempdir()
+            .context("creating the selector shim dir")?;
+        let shim = Self(dir);
+        let script = shim.0.path().join("slurp");
+        let body = format!(
+            "#!/bin/sh\nPATH={}\nexport PATH\ngeometry=$({cmd} \"$@\") || exit\n\
+             printf '%s\\n' \"$geometry\" > {}\nprintf '%s\\n' \"$geometry\"\n",
+            shell_quote(path),
+            shell_quote(&shim.0.path().join("geometry").to_string_lossy()),
+        );
+        fs::write(&script, body).with_context(|| format!("writing {}", script.display()))?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))?;
+        debug!("selector shim at {}", script.display());
+        Ok(shim)
+    }
+
+    /// Put a shim running `selector` first on `cmd`'s PATH, so it runs in
+    /// place of slurp
+    fn install(cmd: &mut Command, selector: &str) -> Result<Self> {
+        let path = env::var_os("PATH").unwrap_or_default();
+        let shim = Self::new(selector, &path.to_string_lossy())?;
+        let mut dirs = vec![shim.0.path().to_path_buf()];
+        dirs.extend(env::split_paths(&path));
+        cmd.env("PATH", env::join_paths(dirs)?);
+        Ok(shim)
+    }
+
+    /// What the selector printed, once it has run
+    fn geometry(&self) -> Option<String> {
+        let geometry = fs::read_to_string(self.0.path().join("geometry")).ok()?;
+        Some(geometry.trim().to_string()).filter(|g| !g.is_empty())
+    }
+}
+
+/// A new, uniquely named `.crabture-tmp-{stem}-*.{ext}` in `dir` and the guard
+/// that deletes it; under `--dry-run` only a made-up name, as nothing may be
+/// created
+fn temp_file(
+    dir: &Path,
+    stem: &str,
+    ext: &str,
+    dry_run: bool,
+) -> Result<(PathBuf, Option<TempPath>)> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if dry_run {
+        return Ok((dir.join(format!(".crabture-tmp-{stem}-XXXXXX.{ext}")), None));
+    }
+    let file = tempfile::Builder::new()
+        .prefix(&format!(".crabture-tmp-{stem}-"))
+        .suffix(&format!(".{ext}"))
+        // tempfile's private default would carry over to the saved screenshot
+        .permissions(fs::Permissions::from_mode(0o666))
+        .tempfile_in(dir)
+        .with_context(|| format!("creating a temp file in {}", dir.display()))?;
+    let guard = file.into_temp_path();
+    Ok((guard.to_path_buf(), Some(guard)))
+}
+
+/// Whether the backend wrote anything; temp files exist empty from the start
+fn has_data(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|m| m.len() > 0)
+}
+
+/// Put an image file on the clipboard with its real MIME type
+fn copy_image(opts: &Options, path: &Path, mime: &str) -> Result<()> {
+    let file = fs::File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut cmd = clipboard(opts, mime);
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .stdin(file)
+        .status()
+        .with_context(|| format!("running {program}"))?;
+    if !status.success() {
+        bail!("{program} failed ({status})");
+    }
+    Ok(())
+}
+
+fn copy_text(opts: &Options, text: &str) -> Result<()> {
+    let mut cmd = clipboard(opts, "text/plain;charset=utf-8");
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("running {program}"))?;
+    child.stdin.take().unwrap().write_all(text.as_bytes())?;
+    let status = child.wait()?;
+    if !status.success() {
+        bail!("{program} failed ({status})");
+    }
+    Ok(())
+}
+
+/// The command that puts `mime` data from stdin on the clipboard
+fn clipboard(opts: &Options, mime: &str) -> Command {
+    let Some(cmdline) = opts.clipboard_cmd.as_deref() else {
+        return opts.backend.clipboard(mime);
+    };
+    // checked to be non-empty in main
+    let mut words = cmdline.split_whitespace();
+    let mut cmd = Command::new(words.next().unwrap_or_default());
+    cmd.args(words.map(|w| w.replace("{mime}", mime)));
+    cmd
+}
+
+/// Fail early if the clipboard tool is missing
+/// Fail early if the backend is missing a program a `how` capture of `kind` needs
+fn require_backend(opts: &Options, kind: CaptureKind, how: SaveHow) -> Result<()> {
+    // a --clipboard-cmd stands in for the backend's clipboard writer
+    let copies = how.copies() && opts.clipboard_cmd.is_none();
+    for tool in opts.backend.required(kind, copies) {
+        tools::require(&tool)?;
+    }
+    Ok(())
+}
+
+fn require_clipboard(opts: &Options) -> Result<()> {
+    let cmd = clipboard(opts, "text/plain");
+    tools::require(Path::new(cmd.get_program()))
+}
+
+/// Launch the default handler without waiting; a missing xdg-open is only a warning
+fn xdg_open(path: &Path) {
+    if which("xdg-open").is_err() {
+        warn!("xdg-open not found, can't open {}", path.display());
+        return;
+    }
+    let _ = Command::new("xdg-open")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// File managers that can highlight a file, by desktop entry, with the argv to do it
+const SELECTING_FILE_MANAGERS: &[(&str, &[&str])] = &[
+    ("org.gnome.Nautilus.desktop", &["nautilus", "--select"]),
+    ("org.kde.dolphin.desktop", &["dolphin", "--select"]),
+    ("nemo.desktop", &["nemo"]),
+];
+
+/// Show the file in the user's file manager, or just open its folder
+fn reveal(path: &Path) {
+    let default = Command::new("xdg-mime")
+        .args(["query", "default", "inode/directory"])
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string());
+    let select = SELECTING_FILE_MANAGERS
+        .iter()
+        .find(|(desktop, argv)| default.as_deref() == Some(desktop) && which(argv[0]).is_ok());
+    match select {
+        Some((_, argv)) => {
+            debug!("revealing with {}", argv[0]);
+            let _ = Command::new(argv[0])
+                .args(&argv[1..])
+                .arg(path)
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+        }
+        None => xdg_open(path.parent().unwrap_or(Path::new("."))),
+    }
+}
+
+const DEFAULT_SOUND: &str = "/usr/share/sounds/freedesktop/stereo/camera-shutter.oga";
+
+/// Fire-and-forget playback; no player installed just means no sound
+fn play_sound(path: &Path) {
+    let path = if path == Path::new("default") {
+        Path::new(DEFAULT_SOUND)
+    } else {
+        path
+    };
+    let Some(player) = ["paplay", "pw-play"].into_iter().find(|p| which(p).is_ok()) else {
+        return;
+    };
+    let _ = Command::new(player)
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+}
+
+/// Move the first choice matching `pred` to the top, keeping the rest in order
+fn promote<T: Copy>(choices: &mut [(&str, T)], pred: impl Fn(T) -> bool) {
+    if let Some(i) = choices.iter().position(|&(_, v)| pred(v)) {
+        choices[..=i].rotate_right(1);
+    }
+}
+
+/// Pick one of labelled values; unknown input falls back like the old string matches
+fn rofi_choose<T: Copy>(
+    opts: &Options,
+    prompt: &str,
+    choices: &[(&str, T)],
+    fallback: T,
+) -> Result<T> {
+    let labels: Vec<&str> = choices.iter().map(|&(label, _)| label).collect();
+    let picked = rofi_pick(opts, prompt, &labels)?;
+    Ok(choices
+        .iter()
+        .find(|&&(label, _)| label == picked)
+        .map_or(fallback, |&(_, v)| v))
+}
+
+fn rofi_pick(opts: &Options, prompt: &str, options: &[&str]) -> Result<String> {
+    let mut cmd = Command::new(&opts.tools.rofi);
+    cmd.args(["-dmenu", "-i", "-no-show-icons", "-p", prompt]);
+    if let Some(c) = &opts.rofi_config {
+        cmd.args(["-config", &c.to_string_lossy()]);
+    }
+    cmd.args(&opts.rofi_args);
+    let input = options.join("\n");
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .context("spawning rofi")?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        stdin.write_all(input.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    // rofi exits 1 on Escape; anything else (bad config, no display) is a real failure
+    match output.status.code() {
+        Some(0) => {}
+        Some(1) => return Err(CaptureError::Cancelled).context("rofi menu"),
+        _ => return Err(CaptureError::Rofi(output.status).into()),
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Wait `secs` before a capture, counting down in notifications. Ctrl-C
+/// cancels it, so nothing is captured and crabture exits with code 2
+fn countdown(secs: u64, opts: &Options) -> Result<()> {
+    watch_interrupts();
+    CATCH_INTERRUPTS.store(true, Ordering::SeqCst);
+    let mut id = None;
+    let counted = if opts.overlay_countdown {
+        overlay_countdown(secs, opts, &mut id)
+    } else {
+        notification_countdown(secs, opts, &mut id)
+    };
+    CATCH_INTERRUPTS.store(false, Ordering::SeqCst);
+    if counted.as_ref().is_err_and(CaptureError::is_cancelled) {
+        let t = &opts.strings;
+        notify(opts, id, &t.countdown_title, &t.countdown_cancelled, None)?;
+    }
+    counted
+}
+
+/// The last seconds of a countdown, which get a notification each
+const FINAL_COUNT: u64 = 3;
+
+/// One notification every `countdown_interval` seconds, landing on the final
+/// 3, 2, 1 whatever the delay
+fn notification_countdown(secs: u64, opts: &Options, id: &mut Option<u32>) -> Result<()> {
+    let t = &opts.strings;
+    let ticks = countdown_ticks(secs, opts.countdown_interval);
+    let next = ticks.iter().skip(1).chain([&0]);
+    for (&left, &next) in ticks.iter().zip(next) {
+        *id = notify(opts, *id, &t.countdown_title, &t.countdown_body(left), None)?;
+        interruptible_sleep(Duration::from_secs(left - next))?;
+    }
+    Ok(())
+}
+
+/// The seconds left at each countdown notification: every `interval` seconds
+/// (at least one) from `secs`, then each of the final ones
+fn countdown_ticks(mut secs: u64, interval: u64) -> Vec<u64> {
+    let mut ticks = Vec::new();
+    while secs > 0 {
+        ticks.push(secs);
+        secs -= match secs {
+            s if s <= FINAL_COUNT => 1,
+            s => (s - FINAL_COUNT).min(interval.max(1)),
+        };
+    }
+    ticks
+}
+
+/// Count down in a single big notification with a progress bar
+fn overlay_countdown(secs: u64, opts: &Options, id: &mut Option<u32>) -> Result<()> {
+    for left in (1..=secs).rev() {
+        let progress = (secs - left) * 100 / secs;
+        *id = send_notification(
+            opts,
+            *id,
+            &opts.strings.countdown_title,
+            &format!("<big><b>{left}</b></big>"),
+            None,
+            &[format!("int:value:{progress}")],
+        );
+        interruptible_sleep(Duration::from_secs(1))?;
+    }
+    Ok(())
+}
+
+/// Whether a countdown or recording is running, which is when Ctrl-C means
+/// "cancel" or "stop" rather than ending the process
+static CATCH_INTERRUPTS: AtomicBool = AtomicBool::new(false);
+/// Set by Ctrl-C during a countdown or recording, for the wait to notice
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Catch SIGINT so a countdown can be cancelled and a recording stopped cleanly.
+/// Outside those it still ends the process right away, the way it always did
+fn watch_interrupts() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        let installed = ctrlc::set_handler(|| {
+            if CATCH_INTERRUPTS.load(Ordering::SeqCst) {
+                INTERRUPTED.store(true, Ordering::SeqCst);
+            } else {
+                std::process::exit(130);
+            }
+        });
+        if let Err(e) = installed {
+            warn!("Ctrl-C can't be caught: {e}");
+        }
+    });
+}
+
+/// Sleep for `dur`, or until Ctrl-C, which is a cancel
+fn interruptible_sleep(dur: Duration) -> Result<()> {
+    let deadline = Instant::now() + dur;
+    loop {
+        if INTERRUPTED.swap(false, Ordering::SeqCst) {
+            return Err(CaptureError::Cancelled).context("countdown interrupted");
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+        sleep(left.min(Duration::from_millis(100)));
+    }
+}
+
+/// Send a notification, replacing `replace` if given; returns the new id when
+/// notify-send can report it. `image` becomes the notification's icon, e.g. a thumbnail of the capture
+fn notify(
+    opts: &Options,
+    replace: Option<u32>,
+    title: &str,
+    body: &str,
+    image: Option<&Path>,
+) -> Result<Option<u32>> {
+    Ok(send_notification(opts, replace, title, body, image, &[]))
+}
+
+/// `--notify-actions`: the save notification with Open and Copy buttons.
+/// notify-send stays alive until the toast is clicked or dismissed and then
+/// prints the key of the chosen action
+fn notify_with_actions(opts: &Options, title: &str, body: &str, image: &Path) -> Option<Child> {
+    if !opts.notify {
+        return None;
+    }
+    let mut cmd = Command::new(&opts.tools.notify_send);
+    if opts.notify_timeout > 0 {
+        cmd.args(["-t", &opts.notify_timeout.to_string()]);
+    }
+    cmd.arg("-i").arg(image);
+    cmd.args(["-A", "open=Open", "-A", "copy=Copy", title, body]);
+    match cmd.stdout(Stdio::piped()).stderr(Stdio::null()).spawn() {
+        Ok(child) => Some(child),
+        Err(e) => {
+            warn!("could not run {}: {e}", opts.tools.notify_send.display());
+            None
+        }
+    }
+}
+
+/// Block until the [`notify_with_actions`] toast goes away and run whatever
+/// button was clicked on `path`
+fn await_action(opts: &Options, child: Child, path: &Path, mime: &str) -> Result<()> {
+    let out = child.wait_with_output()?;
+    if !out.status.success() {
+        warn!("notify-send failed ({}); does it support -A?", out.status);
+        return Ok(());
+    }
+    match String::from_utf8_lossy(&out.stdout).trim() {
+        "open" => xdg_open(path),
+        "copy" => {
+            require_clipboard(opts)?;
+            copy_image(opts, path, mime)?;
+        }
+        "" => debug!("notification dismissed without an action"),
+        other => debug!("ignoring unknown notification action {other:?}"),
+    }
+    Ok(())
+}
+
+/// Set once notify-send rejects `--print-id`, so we stop retrying it
+static NO_PRINT_ID: AtomicBool = AtomicBool::new(false);
+
+fn send_notification(
+    opts: &Options,
+    replace: Option<u32>,
+    title: &str,
+    body: &str,
+    image: Option<&Path>,
+    hints: &[String],
+) -> Option<u32> {
+    if !opts.notify {
+        return None;
+    }
+    let mut cmd = Command::new(&opts.tools.notify_send);
+    if opts.notify_timeout > 0 {
+        cmd.args(["-t", &opts.notify_timeout.to_string()]);
+    }
+    if let Some(image) = image {
+        cmd.arg("-i").arg(image);
+    }
+    for hint in hints {
+        cmd.args(["-h", hint]);
+    }
+    if NO_PRINT_ID.load(Ordering::Relaxed) {
+        let _ = cmd.args([title, body]).status();
+        return None;
+    }
+
+    // the synchronous hint still replaces in place on daemons that ignore -r
+    cmd.args([
+        "-p",
+        "-h",
+        "string:x-canonical-private-synchronous:crabture",
+    ]);
+    if let Some(id) = replace {
+        cmd.args(["-r", &id.to_string()]);
+    }
+    let out = cmd
+        .args([title, body])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        // older notify-send without --print-id/--replace-id
+        NO_PRINT_ID.store(true, Ordering::Relaxed);
+        return send_notification(opts, None, title, body, image, hints);
+    }
+    String::from_utf8_lossy(&out.stdout).trim().parse().ok()
+}
+
+const DEFAULT_TEMPLATE: &str = "screenshot_{day}{month}{year}_{hour}{minute}{second}";
+/// `--iso-names`, for names that sort chronologically
+const ISO_TEMPLATE: &str = "screenshot_{year}-{month}-{day}T{hour}-{minute}-{second}";
+
+/// `ext` is an image [`Format`] or, for recordings, a bare extension
+fn file_name(
+    ext: impl std::fmt::Display,
+    template: Option<&str>,
+    now: &OffsetDateTime,
+    kind: CaptureKind,
+    seq: u32,
+) -> Result<String> {
+    let stem = expand_template(template.unwrap_or(DEFAULT_TEMPLATE), now, kind, seq)?;
+    if stem.trim().is_empty() {
+        bail!("name template produces an empty file name");
+    }
+    if stem.contains('/') || stem.contains(std::path::MAIN_SEPARATOR) {
+        bail!("file name must not contain path separators: {stem}");
+    }
+    Ok(format!("{stem}.{ext}"))
+}
+
+/// A template ending in an image extension carries its own format
+fn split_template_ext(template: &str) -> (&str, Option<Format>) {
+    match template.rsplit_once('.') {
+        Some((stem, ext)) if !stem.is_empty() => match ext.parse() {
+            Ok(fmt) => (stem, Some(fmt)),
+            Err(_) => (template, None),
+        },
+        _ => (template, None),
+    }
+}
+
+fn expand_template(
+    template: &str,
+    now: &OffsetDateTime,
+    kind: CaptureKind,
+    seq: u32,
+) -> Result<String> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('}') else {
+            bail!("unclosed '{{' in name template: {template}");
+        };
+        let token = &rest[start + 1..start + len];
+        match token {
+            "year" => out.push_str(&format!("{:04}", now.year())),
+            "month" => out.push_str(&format!("{:02}", now.month() as u8)),
+            "day" => out.push_str(&format!("{:02}", now.day())),
+            "hour" => out.push_str(&format!("{:02}", now.hour())),
+            "minute" => out.push_str(&format!("{:02}", now.minute())),
+            "second" => out.push_str(&format!("{:02}", now.second())),
+            "kind" => out.push_str(kind.as_str()),
+            "seq" => out.push_str(&seq.to_string()),
+            _ => bail!("unknown token {{{token}}} in name template"),
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// The time names and history entries use: local, unless `--utc` is set or
+/// the local offset can't be determined
+fn capture_time(utc: bool) -> OffsetDateTime {
+    if utc {
+        return OffsetDateTime::now_utc();
+    }
+    OffsetDateTime::now_local().unwrap_or_else(|_| OffsetDateTime::now_utc())
+}
+
+fn mime_type(path: &Path) -> &'static str {
+    Format::from_path(path).unwrap_or(Format::Png).mime()
+}
+
+/// Base for the config and state dirs; never for user-given paths, where a
+/// missing `HOME` is an error instead
+fn home() -> PathBuf {
+    match home_var() {
+        Some(home) => home,
+        None => {
+            // the temp dir at least keeps config and state out of whatever
+            // directory a keybind daemon happened to start us in
+            static WARNED: AtomicBool = AtomicBool::new(false);
+            let temp = env::temp_dir();
+            if !WARNED.swap(true, Ordering::Relaxed) {
+                warn!(
+                    "HOME is not set, looking for config and state under {}",
+                    temp.display()
+                );
+            }
+            temp
+        }
+    }
+}
+
+fn home_var() -> Option<PathBuf> {
+    env::var_os("HOME")
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+/// CLI arg, then `CRABTURE_DIR`, then `XDG_SCREENSHOTS_DIR`, then `~/Pictures`
+/// (an error without `HOME`)
+fn resolve_shot_dir(cli_dir: Option<PathBuf>) -> Result<PathBuf> {
+    if let Some(dir) = cli_dir {
+        return expand_path(&dir);
+    }
+    if let Some(dir) = env::var_os("CRABTURE_DIR").filter(|d| !d.is_empty()) {
+        let dir = expand_path(Path::new(&dir))?;
+        // a keybind daemon's working dir is anyone's guess
+        if dir.is_relative() {
+            bail!(
+                "CRABTURE_DIR must be an absolute path, got {}",
+                dir.display()
+            );
+        }
+        return Ok(dir);
+    }
+    if let Some(dir) = xdg_screenshots_dir()? {
+        return Ok(dir);
+    }
+    match home_var() {
+        Some(home) => Ok(home.join("Pictures")),
+        None => bail!("HOME is not set, so there is no ~/Pictures; pass --dir or set CRABTURE_DIR"),
+    }
+}
+
+/// Expand a leading `~` and `$VAR`/`${VAR}` references; unset variables are
+/// left as written so the resulting path still points at the mistake. A `~`
+/// without `HOME` is an error, as there is nowhere sensible to put it
+fn expand_path(path: &Path) -> Result<PathBuf> {
+    let Some(raw) = path.to_str() else {
+        return Ok(path.to_path_buf());
+    };
+    let raw = match raw.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let home = home_var().with_context(|| {
+                format!("HOME is not set, so {raw} can't be expanded; use an absolute path")
+            })?;
+            format!("{}{rest}", home.display())
+        }
+        _ => raw.to_string(),
+    };
+
+    let mut out = String::with_capacity(raw.len());
+    let mut rest = raw.as_str();
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let (name, tail) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &braced[end + 1..]),
+                None => ("", after),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], &after[end..])
+            }
+        };
+        match env::var(name) {
+            Ok(value) if !name.is_empty() => out.push_str(&value),
+            // unset variable, lone `$` or unclosed `${`: keep the text as written
+            _ => out.push_str(&rest[pos..rest.len() - tail.len()]),
+        }
+        rest = tail;
+    }
+    out.push_str(rest);
+    Ok(PathBuf::from(out))
+}
+
+/// Create the dir and prove a file can be written there, so a read-only
+/// target fails up front instead of after the capture; skipped under
+/// `--dry-run`, as nothing may be created
+fn ensure_writable(dir: &Path, dry_run: bool) -> Result<()> {
+    if dry_run {
+        return Ok(());
+    }
+    fs::create_dir_all(dir)
+        .with_context(|| format!("cannot create screenshot dir {}", dir.display()))?;
+    let probe = dir.join(format!(".crabture-write-test-{}", std::process::id()));
+    fs::File::create(&probe)
+        .with_context(|| format!("screenshot dir {} is not writable", dir.display()))?;
+    fs::remove_file(&probe).ok();
+    Ok(())
+}
+
+fn xdg_screenshots_dir() -> Result<Option<PathBuf>> {
+    if let Ok(path) = env::var("XDG_SCREENSHOTS_DIR") {
+        return expand_path(Path::new(&path)).map(Some);
+    }
+    let Some(home) = home_var() else {
+        return Ok(None);
+    };
+    if let Ok(s) = fs::read_to_string(home.join(".config/user-dirs.dirs")) {
+        for line in s.lines() {
+            if let Some(rest) = line.strip_prefix("XDG_SCREENSHOTS_DIR=") {
+                let raw = rest.trim().trim_matches('"');
+                return expand_path(Path::new(raw)).map(Some);
+            }
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tests touching the process environment must not run concurrently
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn set_env(key: &str, value: Option<&str>) {
+        // SAFETY: every test that touches the environment holds ENV_LOCK
+        unsafe {
+            match value {
+                Some(v) => env::set_var(key, v),
+                None => env::remove_var(key),
+            }
+        }
+    }
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(std::iter::once("crabture").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn instant_flags_skip_the_menu() {
+        for (flag, shot) in [
+            ("--instant", (CaptureKind::Screen, SaveHow::Save)),
+            ("--instant-area", (CaptureKind::Area, SaveHow::Save)),
+            ("--instant-output", (CaptureKind::Output, SaveHow::Save)),
+            ("--window", (CaptureKind::ActiveWindow, SaveHow::Save)),
+            ("--area-edit", (CaptureKind::Area, SaveHow::Edit)),
+        ] {
+            let mut cli = parse(&[flag]).unwrap();
+            cli.apply_config(Config::default());
+            assert_eq!(cli.instant_shot(), Some(shot), "{flag}");
+            assert!(!cli.interactive, "{flag}");
+        }
+    }
+
+    #[test]
+    fn quality_is_checked_at_parse_time() {
+        assert_eq!(parse(&["--quality", "100"]).unwrap().quality, Some(100));
+        assert!(parse(&["--quality", "150"]).is_err());
+    }
+
+    #[test]
+    fn shadow_colors() {
+        assert_eq!(shadow::parse_color("#102030"), Ok([16, 32, 48, 255]));
+        assert_eq!(shadow::parse_color("10203040"), Ok([16, 32, 48, 64]));
+        assert!(shadow::parse_color("#12345").is_err());
+        // multibyte input of a valid length must not be sliced mid-char
+        assert!(shadow::parse_color("#aé€").is_err());
+    }
+
+    #[test]
+    fn no_mode_flag_means_interactive() {
+        let mut cli = parse(&[]).unwrap();
+        cli.apply_config(Config::default());
+        assert_eq!(cli.instant_shot(), None);
+        assert!(cli.interactive);
+    }
+
+    #[test]
+    fn mode_flags_are_mutually_exclusive() {
+        for args in [
+            ["--instant", "--interactive"],
+            ["--instant", "--instant-area"],
+            ["--instant-area", "--interactive"],
+            ["--instant-output", "--window"],
+        ] {
+            let err = parse(&args).unwrap_err();
+            assert_eq!(
+                err.kind(),
+                clap::error::ErrorKind::ArgumentConflict,
+                "{args:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn interactive_flag_overrides_configured_mode() {
+        let cfg = || Config {
+            mode: Some(Mode::Instant),
+            ..Config::default()
+        };
+
+        let mut cli = parse(&[]).unwrap();
+        cli.apply_config(cfg());
+        assert_eq!(
+            cli.instant_shot(),
+            Some((CaptureKind::Screen, SaveHow::Save))
+        );
+
+        let mut cli = parse(&["--interactive"]).unwrap();
+        cli.apply_config(cfg());
+        assert_eq!(cli.instant_shot(), None);
+    }
+
+    #[test]
+    fn shot_dir_resolution_order() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let home = env::temp_dir().join(format!("crabture-test-{}", std::process::id()));
+        set_env("HOME", Some(home.to_str().unwrap()));
+        set_env("CRABTURE_DIR", Some("/crab"));
+        set_env("XDG_SCREENSHOTS_DIR", Some("/xdg"));
+
+        let cli = Some(PathBuf::from("/cli"));
+        assert_eq!(resolve_shot_dir(cli).unwrap(), Path::new("/cli"));
+        assert_eq!(resolve_shot_dir(None).unwrap(), Path::new("/crab"));
+
+        set_env("CRABTURE_DIR", Some(""));
+        assert_eq!(resolve_shot_dir(None).unwrap(), Path::new("/xdg"));
+
+        set_env("CRABTURE_DIR", None);
+        assert_eq!(resolve_shot_dir(None).unwrap(), Path::new("/xdg"));
+
+        set_env("XDG_SCREENSHOTS_DIR", None);
+        assert_eq!(resolve_shot_dir(None).unwrap(), home.join("Pictures"));
+    }
+
+    #[test]
+    fn relative_crabture_dir_is_rejected() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        set_env("CRABTURE_DIR", Some("shots"));
+        let err = resolve_shot_dir(None).unwrap_err();
+        set_env("CRABTURE_DIR", None);
+        assert!(err.to_string().contains("absolute"), "{err}");
+    }
+
+    #[test]
+    fn expand_path_forms() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        set_env("HOME", Some("/home/crab"));
+        set_env("CRABTURE_UNSET_VAR", None);
+        for (raw, want) in [
+            ("~", "/home/crab"),
+            ("~/Shots", "/home/crab/Shots"),
+            ("$HOME/Shots", "/home/crab/Shots"),
+            ("${HOME}/Shots", "/home/crab/Shots"),
+            ("/a/${HOME}x", "/a//home/crabx"),
+            ("~user/Shots", "~user/Shots"),
+            ("/a/~/b", "/a/~/b"),
+            ("$CRABTURE_UNSET_VAR/x", "$CRABTURE_UNSET_VAR/x"),
+            ("/price$/x", "/price$/x"),
+            ("/a/${HOME", "/a/${HOME"),
+        ] {
+            assert_eq!(
+                expand_path(Path::new(raw)).unwrap(),
+                Path::new(want),
+                "{raw}"
+            );
+        }
+        set_env("HOME", None);
+        assert!(expand_path(Path::new("~/Shots")).is_err());
+        assert_eq!(
+            expand_path(Path::new("/Shots")).unwrap(),
+            Path::new("/Shots")
+        );
+    }
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("crabture-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn cross_device(_: &Path, _: &Path) -> std::io::Result<()> {
+        Err(std::io::ErrorKind::CrossesDevices.into())
+    }
+
+    #[test]
+    fn move_falls_back_to_copy_across_devices() {
+        let dir = scratch_dir("move-copy");
+        let (src, dest) = (dir.join("src.png"), dir.join("dest.png"));
+        fs::write(&src, b"pixels").unwrap();
+
+        place_file_with(&src, &dest, false, cross_device, cross_device).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"pixels");
+        assert!(!src.exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn move_fails_when_copy_fails_too() {
+        let dir = scratch_dir("move-fail");
+        let src = dir.join("src.png");
+        fs::write(&src, b"pixels").unwrap();
+        let dest = dir.join("missing").join("dest.png");
+
+        assert!(place_file_with(&src, &dest, false, cross_device, cross_device).is_err());
+        assert!(src.exists(), "source must survive a failed save");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn default_file_name_for_each_format() {
+        let now = time::macros::datetime!(2024-03-05 07:08:09 UTC);
+        for (fmt, want) in [
+            ("png", "screenshot_05032024_070809.png"),
+            ("jpg", "screenshot_05032024_070809.jpg"),
+            ("jpeg", "screenshot_05032024_070809.jpg"),
+            ("webp", "screenshot_05032024_070809.webp"),
+        ] {
+            let fmt: Format = fmt.parse().unwrap();
+            let name = file_name(fmt, None, &now, CaptureKind::Screen, 1).unwrap();
+            assert_eq!(name, want);
+        }
+        assert!("bmp".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn template_file_names() {
+        let now = time::macros::datetime!(2024-12-31 23:59:01 UTC);
+        let name = |t: &str, kind, seq| file_name(Format::Png, Some(t), &now, kind, seq);
+        assert_eq!(
+            name(
+                "{year}-{month}-{day}_{hour}{minute}{second}",
+                CaptureKind::Screen,
+                1
+            )
+            .unwrap(),
+            "2024-12-31_235901.png"
+        );
+        assert_eq!(
+            name("{kind}_{seq}", CaptureKind::Area, 7).unwrap(),
+            "area_7.png"
+        );
+        assert!(name("{nope}", CaptureKind::Screen, 1).is_err());
+        assert!(name("{year", CaptureKind::Screen, 1).is_err());
+        assert!(name("a/{seq}", CaptureKind::Screen, 1).is_err());
+        assert!(name("  ", CaptureKind::Screen, 1).is_err());
+    }
+
+    #[test]
+    fn colliding_names_get_a_suffix() {
+        let taken = ["shot.png", "shot-1.png", "notes"];
+        let is_taken = |n: &str| taken.contains(&n);
+        assert_eq!(unclobbered_name("free.png", is_taken), "free.png");
+        assert_eq!(unclobbered_name("shot.png", is_taken), "shot-2.png");
+        assert_eq!(unclobbered_name("notes", is_taken), "notes-1");
+    }
+
+    #[test]
+    fn countdown_ticks_land_on_the_final_count() {
+        assert!(countdown_ticks(0, 5).is_empty());
+        assert_eq!(countdown_ticks(1, 5), [1]);
+        assert_eq!(countdown_ticks(3, 5), [3, 2, 1]);
+        assert_eq!(countdown_ticks(4, 5), [4, 3, 2, 1]);
+        assert_eq!(countdown_ticks(60, 10), [60, 50, 40, 30, 20, 10, 3, 2, 1]);
+        // a zero interval must still make progress
+        assert_eq!(countdown_ticks(60, 0).len(), 60);
+        assert_eq!(countdown_ticks(4, 0), [4, 3, 2, 1]);
     }
-    Ok(())
 }
@@ -0,0 +1,153 @@
+use anyhow::{Context, Result};
+use exif::{Field, In, Tag, Value, experimental::Writer};
+use log::debug;
+use std::{fs, io::Cursor, path::Path};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339, macros::format_description};
+
+use crate::format::Format;
+
+/// What `take` knows about a capture, written into the saved file unless
+/// `--no-metadata`
+pub struct Info {
+    pub taken: OffsetDateTime,
+    pub kind: &'static str,
+    pub output: Option<String>,
+    /// slurp-style `x,y wxh`
+    pub geometry: Option<String>,
+    /// crabture's version and the capture tool's
+    pub software: String,
+}
+
+impl Info {
+    fn description(&self) -> String {
+        let mut text = format!("{} capture", self.kind);
+        if let Some(output) = &self.output {
+            text.push_str(&format!(" of {output}"));
+        }
+        if let Some(geometry) = &self.geometry {
+            text.push_str(&format!(" at {geometry}"));
+        }
+        text
+    }
+}
+
+/// Add `info` to the image at `path`, as text chunks in a png or an EXIF
+/// segment in a jpg. Webp, and files that aren't really `format`, are left alone
+pub fn embed(path: &Path, format: Format, info: &Info) -> Result<()> {
+    let data = fs::read(path).context("reading capture for metadata")?;
+    let tagged = match format {
+        Format::Png => with_png_text(&data, info)?,
+        Format::Jpg => with_exif(&data, info)?,
+        Format::Webp => None,
+    };
+    match tagged {
+        Some(bytes) => fs::write(path, bytes).context("writing capture metadata"),
+        None => {
+            debug!("not adding metadata to {}", path.display());
+            Ok(())
+        }
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// `data` with tEXt chunks right after IHDR, or `None` if it isn't a png
+fn with_png_text(data: &[u8], info: &Info) -> Result<Option<Vec<u8>>> {
+    if !data.starts_with(PNG_SIGNATURE) || data.get(12..16) != Some(b"IHDR") {
+        return Ok(None);
+    }
+    let ihdr_len = u32::from_be_bytes(data[8..12].try_into()?) as usize;
+    // length, type, data and crc
+    let split = PNG_SIGNATURE.len() + 12 + ihdr_len;
+    if data.len() < split {
+        return Ok(None);
+    }
+    let mut chunks = vec![
+        ("Creation Time", info.taken.format(&Rfc3339)?),
+        ("Software", info.software.clone()),
+        ("Description", info.description()),
+    ];
+    if let Some(output) = &info.output {
+        chunks.push(("Source", output.clone()));
+    }
+
+    let mut out = data[..split].to_vec();
+    for (keyword, text) in chunks {
+        // tEXt is Latin-1; everything here is ASCII except odd output names
+        let text: String = text
+            .chars()
+            .map(|c| if c.is_ascii() { c } else { '?' })
+            .collect();
+        let mut body = b"tEXt".to_vec();
+        body.extend_from_slice(keyword.as_bytes());
+        body.push(0);
+        body.extend_from_slice(text.as_bytes());
+        out.extend_from_slice(&(body.len() as u32 - 4).to_be_bytes());
+        out.extend_from_slice(&body);
+        out.extend_from_slice(&crc32(&body).to_be_bytes());
+    }
+    out.extend_from_slice(&data[split..]);
+    Ok(Some(out))
+}
+
+/// The CRC png chunks end with, over the chunk type and data
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= u32::from(b);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// `data` with an EXIF APP1 segment, or `None` if it isn't a jpeg
+fn with_exif(data: &[u8], info: &Info) -> Result<Option<Vec<u8>>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Ok(None);
+    }
+    let ascii = |text: String| Value::Ascii(vec![text.into_bytes()]);
+    let stamp = info.taken.format(format_description!(
+        "[year]:[month]:[day] [hour]:[minute]:[second]"
+    ))?;
+    let fields = [
+        (Tag::DateTimeOriginal, ascii(stamp)),
+        (Tag::Software, ascii(info.software.clone())),
+        (Tag::ImageDescription, ascii(info.description())),
+    ]
+    .map(|(tag, value)| Field {
+        tag,
+        ifd_num: In::PRIMARY,
+        value,
+    });
+    let mut writer = Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut tiff = Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).context("encoding EXIF")?;
+    let tiff = tiff.into_inner();
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&u16::try_from(tiff.len() + 8)?.to_be_bytes());
+    segment.extend_from_slice(b"Exif\0\0");
+    segment.extend_from_slice(&tiff);
+
+    // after a JFIF APP0 if there is one, which has to come first
+    let mut at = 2;
+    if data.get(2..4) == Some(&[0xFF, 0xE0])
+        && let Some(len) = data.get(4..6)
+    {
+        at += 2 + usize::from(u16::from_be_bytes([len[0], len[1]]));
+    }
+    let at = at.min(data.len());
+    let mut out = data[..at].to_vec();
+    out.extend_from_slice(&segment);
+    out.extend_from_slice(&data[at..]);
+    Ok(Some(out))
+}
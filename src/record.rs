@@ -0,0 +1,333 @@
+use anyhow::{Context, Result, bail};
+use log::{debug, warn};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::atomic::Ordering,
+    thread::sleep,
+    time::Duration,
+};
+use which::which;
+
+use crate::{
+    CATCH_INTERRUPTS, CaptureKind, DEFAULT_TEMPLATE, INTERRUPTED, Options, capture_time,
+    command_line, countdown, file_name, has_data, hypr, notify, select_area, state, temp_file,
+    tools, unclobbered_name, watch_interrupts,
+};
+
+/// Frame rate of `--gif` output; more mostly buys size, not smoothness
+const GIF_FPS: u32 = 15;
+
+/// Where the running recorder's pid is kept, so the next `--record` stops it
+fn pid_file() -> PathBuf {
+    state::dir().join("recording.pid")
+}
+
+/// The recorder started by an earlier run, if it is still going
+fn running() -> Option<u32> {
+    let pid: u32 = fs::read_to_string(pid_file()).ok()?.trim().parse().ok()?;
+    let cmdline = fs::read(format!("/proc/{pid}/cmdline")).ok()?;
+    String::from_utf8_lossy(&cmdline)
+        .contains("wf-recorder")
+        .then_some(pid)
+}
+
+/// `--record`: stop the recording an earlier run started, or start one of
+/// `kind` and wait until it is stopped (by the next `--record` or Ctrl-C)
+pub fn toggle(kind: CaptureKind, delay: Option<u64>, opts: &Options) -> Result<()> {
+    if let Some(pid) = running() {
+        if opts.dry_run {
+            println!("kill -INT {pid}");
+            return Ok(());
+        }
+        debug!("stopping wf-recorder ({pid})");
+        return interrupt(pid);
+    }
+    let _ = fs::remove_file(pid_file());
+
+    require(kind)?;
+    let dest = opts.shot_dir.join(video_name(kind, "mp4", opts)?);
+    if let Some(secs) = delay.filter(|_| !opts.dry_run) {
+        countdown(secs, opts)?;
+    }
+    let mut cmd = command(kind, opts, &dest)?;
+    if opts.dry_run {
+        println!("{}", command_line(&cmd));
+        return Ok(());
+    }
+
+    let child = spawn(&mut cmd)?;
+    let pid_path = pid_file();
+    if let Some(dir) = pid_path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+    }
+    fs::write(&pid_path, child.id().to_string())
+        .with_context(|| format!("writing {}", pid_path.display()))?;
+    notify(
+        opts,
+        None,
+        "Recording started",
+        "Run crabture --record again to stop",
+        None,
+    )?;
+
+    watch_interrupts();
+    CATCH_INTERRUPTS.store(true, Ordering::SeqCst);
+    let finished = record_until_stopped(child, &dest);
+    CATCH_INTERRUPTS.store(false, Ordering::SeqCst);
+    let _ = fs::remove_file(&pid_path);
+    if let Err(e) = finished {
+        notify(opts, None, "Recording failed", &format!("{e:#}"), None)?;
+        return Err(e);
+    }
+    notify(
+        opts,
+        None,
+        "Recording saved",
+        &format!("DIR: {}", opts.shot_dir.display()),
+        None,
+    )?;
+    if opts.print_path {
+        println!("{}", std::path::absolute(&dest)?.display());
+    }
+    Ok(())
+}
+
+/// `--gif`: record `kind` for `secs` seconds and turn it into a gif, through
+/// gifski when it is installed and ffmpeg's palette filters otherwise
+pub fn gif(kind: CaptureKind, secs: u64, delay: Option<u64>, opts: &Options) -> Result<()> {
+    require(kind)?;
+    tools::require(Path::new("ffmpeg")).context("--gif converts the recording with ffmpeg")?;
+    let name = video_name(kind, "gif", opts)?;
+    let dest = opts.shot_dir.join(&name);
+    let (video, _video_guard) = temp_file(
+        &opts.shot_dir,
+        name.trim_end_matches(".gif"),
+        "mp4",
+        opts.dry_run,
+    )?;
+    if let Some(secs) = delay.filter(|_| !opts.dry_run) {
+        countdown(secs, opts)?;
+    }
+    let mut cmd = command(kind, opts, &video)?;
+    if opts.dry_run {
+        println!("{}", command_line(&cmd));
+        return Ok(());
+    }
+
+    let child = spawn(&mut cmd)?;
+    notify(
+        opts,
+        None,
+        "Recording gif",
+        &format!("{secs} seconds"),
+        None,
+    )?;
+    sleep(Duration::from_secs(secs));
+    interrupt(child.id())?;
+    finish(child, &video)?;
+
+    to_gif(&video, &dest)?;
+    if !has_data(&dest) {
+        let _ = fs::remove_file(&dest);
+        bail!("the gif conversion wrote nothing");
+    }
+    let path = std::path::absolute(&dest)?;
+    let size = fs::metadata(&path)?.len();
+    notify(
+        opts,
+        None,
+        "GIF saved",
+        &format!("{} ({})", path.display(), human_size(size)),
+        None,
+    )?;
+    if opts.print_path {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+fn require(kind: CaptureKind) -> Result<()> {
+    tools::require(Path::new("wf-recorder")).context("recording needs wf-recorder")?;
+    if kind == CaptureKind::Area {
+        tools::require(Path::new("slurp")).context("recording an area needs slurp")?;
+    }
+    Ok(())
+}
+
+/// A free name in the screenshot dir; without a template it says "recording"
+/// where screenshots say "screenshot"
+fn video_name(kind: CaptureKind, ext: &str, opts: &Options) -> Result<String> {
+    let template = match &opts.name_template {
+        Some(t) => t.clone(),
+        None => DEFAULT_TEMPLATE.replacen("screenshot", "recording", 1),
+    };
+    let name = file_name(ext, Some(&template), &capture_time(opts.utc), kind, 1)?;
+    Ok(if opts.overwrite {
+        name
+    } else {
+        unclobbered_name(&name, |n| opts.shot_dir.join(n).exists())
+    })
+}
+
+/// wf-recorder writing `kind` to `dest`. Areas are selected here, so under
+/// `--dry-run` the geometry is only a placeholder
+fn command(kind: CaptureKind, opts: &Options, dest: &Path) -> Result<Command> {
+    let mut cmd = Command::new("wf-recorder");
+    // don't stop to ask about an existing file; the name is already free
+    cmd.arg("-y");
+    match kind {
+        // wf-recorder records one output at a time, so the whole screen
+        // means the focused one unless --output says otherwise
+        CaptureKind::Screen | CaptureKind::Output => {
+            let name = match &opts.output {
+                Some(name) => name.clone(),
+                None => hypr::monitors()?
+                    .into_iter()
+                    .find(|m| m.focused)
+                    .map(|m| m.name)
+                    .context("no focused output; pass --output NAME")?,
+            };
+            cmd.args(["-o", &name]);
+        }
+        CaptureKind::ActiveWindow => {
+            let win = hypr::active_window()?.context("no window is focused")?;
+            cmd.args([
+                "-g",
+                &format!("{},{} {}x{}", win.at.0, win.at.1, win.size.0, win.size.1),
+            ]);
+        }
+        CaptureKind::Area if opts.dry_run => {
+            cmd.args(["-g", "X,Y WxH"]);
+        }
+        CaptureKind::Area => {
+            cmd.args(["-g", &select_area(opts)?]);
+        }
+    }
+    cmd.arg("-f").arg(dest);
+    Ok(cmd)
+}
+
+fn spawn(cmd: &mut Command) -> Result<Child> {
+    debug!("running {}", command_line(cmd));
+    // stderr stays ours: wf-recorder can log for the whole recording, which
+    // would fill a pipe nobody reads until it exits
+    cmd.stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .spawn()
+        .context("running wf-recorder")
+}
+
+/// wf-recorder finishes the file on SIGINT, same as Ctrl-C
+fn interrupt(pid: u32) -> Result<()> {
+    let status = Command::new("kill")
+        .args(["-INT", &pid.to_string()])
+        .status()
+        .context("running kill")?;
+    if !status.success() {
+        bail!("could not stop the recording ({pid})");
+    }
+    Ok(())
+}
+
+/// Wait for the recorder to be stopped by the next `--record`, or stop it
+/// ourselves on Ctrl-C, so either way ends with a finished video
+fn record_until_stopped(mut child: Child, dest: &Path) -> Result<()> {
+    while child
+        .try_wait()
+        .context("waiting for wf-recorder")?
+        .is_none()
+    {
+        // from a terminal wf-recorder got the SIGINT too and may be gone already
+        if INTERRUPTED.swap(false, Ordering::SeqCst)
+            && let Err(e) = interrupt(child.id())
+        {
+            debug!("{e:#}");
+        }
+        sleep(Duration::from_millis(100));
+    }
+    finish(child, dest)
+}
+
+fn finish(mut child: Child, dest: &Path) -> Result<()> {
+    let status = child.wait().context("waiting for wf-recorder")?;
+    if !has_data(dest) {
+        let _ = fs::remove_file(dest);
+        bail!("wf-recorder failed ({status}) without writing a video");
+    }
+    if !status.success() {
+        warn!("wf-recorder exited with {status}, the video may be incomplete");
+    }
+    Ok(())
+}
+
+/// gifski makes much smaller gifs, from png frames ffmpeg extracts; without it
+/// ffmpeg builds a palette and encodes in one go
+fn to_gif(video: &Path, dest: &Path) -> Result<()> {
+    let fps = format!("fps={GIF_FPS}");
+    if which("gifski").is_err() {
+        debug!("gifski not found, converting with ffmpeg alone");
+        let mut ffmpeg = Command::new("ffmpeg");
+        ffmpeg
+            .args(["-v", "error", "-y", "-i"])
+            .arg(video)
+            .args([
+                "-vf",
+                &format!("{fps},split[a][b];[a]palettegen[p];[b][p]paletteuse"),
+            ])
+            .arg(dest);
+        return run(ffmpeg);
+    }
+
+    let frames = tempfile::tempdir().context("creating a temp dir for the gif frames")?;
+    let mut ffmpeg = Command::new("ffmpeg");
+    ffmpeg
+        .args(["-v", "error", "-i"])
+        .arg(video)
+        .args(["-vf", &fps])
+        .arg(frames.path().join("frame%05d.png"));
+    run(ffmpeg)?;
+    let mut paths = fs::read_dir(frames.path())?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    if paths.is_empty() {
+        bail!("ffmpeg extracted no frames from the recording");
+    }
+    paths.sort();
+    let mut gifski = Command::new("gifski");
+    gifski
+        .args(["--quiet", "--fps", &GIF_FPS.to_string(), "-o"])
+        .arg(dest)
+        .args(&paths);
+    run(gifski)
+}
+
+fn run(mut cmd: Command) -> Result<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    debug!("running {}", command_line(&cmd));
+    let out = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .with_context(|| format!("running {program}"))?;
+    if !out.status.success() {
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        match stderr.trim() {
+            "" => bail!("{program} failed ({})", out.status),
+            err => bail!("{program} failed ({}): {err}", out.status),
+        }
+    }
+    Ok(())
+}
+
+fn human_size(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+    match bytes {
+        b if b >= MIB => format!("{:.1} MiB", b as f64 / MIB as f64),
+        b if b >= KIB => format!("{} KiB", b / KIB),
+        b => format!("{b} B"),
+    }
+}
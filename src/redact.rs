@@ -0,0 +1,186 @@
+use anyhow::{Context, Result, bail};
+use clap::ValueEnum;
+use image::{Rgba, imageops};
+use log::debug;
+use serde::Deserialize;
+use std::{
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{CaptureKind, format, hypr};
+
+/// How `--redact` hides the selected rectangles
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Style {
+    /// A blur too strong to read text through
+    #[default]
+    Blur,
+    /// Solid black boxes
+    Black,
+}
+
+/// A slurp `x,y wxh` geometry, in the compositor's logical coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+impl Rect {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (pos, size) = s.trim().split_once(' ')?;
+        let (x, y) = pos.split_once(',')?;
+        let (w, h) = size.split_once('x')?;
+        Some(Self {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+            w: w.parse().ok()?,
+            h: h.parse().ok()?,
+        })
+    }
+}
+
+/// The logical rectangle a capture of `kind` covered. `area` is the geometry
+/// the area selection returned
+pub fn captured(kind: CaptureKind, output: Option<&str>, area: Option<&str>) -> Result<Rect> {
+    match kind {
+        CaptureKind::Area => area
+            .and_then(Rect::parse)
+            .context("the area selection's geometry is unknown"),
+        CaptureKind::ActiveWindow => {
+            let win = hypr::active_window()?.context("no window is focused")?;
+            Ok(Rect {
+                x: f64::from(win.at.0),
+                y: f64::from(win.at.1),
+                w: f64::from(win.size.0),
+                h: f64::from(win.size.1),
+            })
+        }
+        CaptureKind::Output => {
+            let monitors = hypr::monitors()?;
+            let monitor = match output {
+                Some(name) => monitors.into_iter().find(|m| m.name == name),
+                None => monitors.into_iter().find(|m| m.focused),
+            }
+            .context("the captured output is gone")?;
+            Ok(logical(&monitor))
+        }
+        CaptureKind::Screen => {
+            let rects: Vec<_> = hypr::monitors()?.iter().map(logical).collect();
+            if rects.is_empty() {
+                bail!("hyprctl reported no monitors");
+            }
+            let (x, y) = rects
+                .iter()
+                .fold((f64::MAX, f64::MAX), |(x, y), r| (x.min(r.x), y.min(r.y)));
+            let (right, bottom) = rects.iter().fold((f64::MIN, f64::MIN), |(rx, by), r| {
+                (rx.max(r.x + r.w), by.max(r.y + r.h))
+            });
+            Ok(Rect {
+                x,
+                y,
+                w: right - x,
+                h: bottom - y,
+            })
+        }
+    }
+}
+
+/// Monitor sizes are in pixels, positions already logical
+fn logical(m: &hypr::Monitor) -> Rect {
+    Rect {
+        x: f64::from(m.x),
+        y: f64::from(m.y),
+        w: f64::from(m.width) / m.scale,
+        h: f64::from(m.height) / m.scale,
+    }
+}
+
+/// Let the user drag out rectangles with slurp until they press Escape. slurp
+/// failing outright is an error, so the capture isn't saved unredacted
+pub fn select() -> Result<Vec<Rect>> {
+    let mut rects = Vec::new();
+    loop {
+        let out = Command::new("slurp")
+            .stderr(Stdio::piped())
+            .output()
+            .context("running slurp")?;
+        let geometry = String::from_utf8_lossy(&out.stdout);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        let stderr = stderr.trim();
+        match out.status.code() {
+            Some(0) if !geometry.trim().is_empty() => match Rect::parse(&geometry) {
+                Some(rect) => rects.push(rect),
+                None => bail!("slurp printed an unexpected geometry: {}", geometry.trim()),
+            },
+            // slurp exits 1 for any failure; Escape says so or prints nothing
+            Some(0 | 1) if stderr.is_empty() || stderr.ends_with("selection cancelled") => {
+                debug!("redaction done after {} rectangle(s)", rects.len());
+                return Ok(rects);
+            }
+            _ if stderr.is_empty() => bail!("slurp failed ({})", out.status),
+            _ => bail!("slurp failed ({}): {stderr}", out.status),
+        }
+    }
+}
+
+/// `rect` in the pixels of a `width` x `height` image that covered `captured`
+/// on screen, as `(x, y, w, h)`; `None` if they don't overlap
+fn pixels(width: u32, height: u32, captured: Rect, rect: Rect) -> Option<(u32, u32, u32, u32)> {
+    // image pixels per logical pixel, which is the monitor scale for most kinds
+    let scale = f64::from(width) / captured.w;
+    let to_px = |v: f64, max: u32| (v * scale).round().clamp(0.0, f64::from(max)) as u32;
+    let (x0, y0) = (
+        to_px(rect.x - captured.x, width),
+        to_px(rect.y - captured.y, height),
+    );
+    let (x1, y1) = (
+        to_px(rect.x + rect.w - captured.x, width),
+        to_px(rect.y + rect.h - captured.y, height),
+    );
+    (x1 > x0 && y1 > y0).then_some((x0, y0, x1 - x0, y1 - y0))
+}
+
+/// Cut the image at `path`, which covered `captured` on screen, down to `rect`
+pub fn crop(path: &Path, captured: Rect, rect: Rect) -> Result<()> {
+    let (img, file_format) = format::load_as(path, "cropping")?;
+    let img = img.to_rgba8();
+    let (x, y, w, h) = pixels(img.width(), img.height(), captured, rect)
+        .context("the selection is outside the capture")?;
+    let cropped = imageops::crop_imm(&img, x, y, w, h).to_image();
+    format::save(path, cropped, file_format, "cropped")
+}
+
+/// Hide `rects` in the image at `path`, which covered `captured` on screen
+pub fn apply(path: &Path, captured: Rect, rects: &[Rect], style: Style) -> Result<()> {
+    let (img, file_format) = format::load_as(path, "redaction")?;
+    let mut img = img.to_rgba8();
+    for &rect in rects {
+        let Some((x0, y0, w, h)) = pixels(img.width(), img.height(), captured, rect) else {
+            debug!("rectangle {rect:?} is outside the capture");
+            continue;
+        };
+        let (x1, y1) = (x0 + w, y0 + h);
+        match style {
+            Style::Black => {
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        img.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+            Style::Blur => {
+                let region = imageops::crop_imm(&img, x0, y0, w, h).to_image();
+                let sigma = (w.min(h) as f32 / 4.0).max(8.0);
+                let blurred = imageops::blur(&region, sigma);
+                imageops::replace(&mut img, &blurred, i64::from(x0), i64::from(y0));
+            }
+        }
+    }
+
+    format::save(path, img, file_format, "redacted")
+}
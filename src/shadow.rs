@@ -0,0 +1,92 @@
+use anyhow::Result;
+use image::{Rgba, RgbaImage, imageops};
+use std::path::Path;
+
+use crate::format;
+
+/// Look of the `--shadow` frame
+#[derive(Clone, Copy, Debug)]
+pub struct Shadow {
+    pub padding: u32,
+    pub blur: f32,
+    pub radius: u32,
+    pub color: [u8; 4],
+}
+
+/// `#rrggbb` or `#rrggbbaa`, for clap
+pub fn parse_color(s: &str) -> Result<[u8; 4], String> {
+    let hex = s.strip_prefix('#').unwrap_or(s);
+    // checked first, so the byte slicing below stays on char boundaries
+    if !hex.is_ascii() {
+        return Err(format!("invalid hex color {s:?}"));
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    let parsed = match hex.len() {
+        6 => (byte(0), byte(2), byte(4), Ok(255)),
+        8 => (byte(0), byte(2), byte(4), byte(6)),
+        _ => return Err(format!("expected #rrggbb or #rrggbbaa, got {s:?}")),
+    };
+    match parsed {
+        (Ok(r), Ok(g), Ok(b), Ok(a)) => Ok([r, g, b, a]),
+        _ => Err(format!("invalid hex color {s:?}")),
+    }
+}
+
+/// Round the corners of the png at `path` and put it on a transparent canvas
+/// with a soft drop shadow underneath
+pub fn apply(path: &Path, shadow: &Shadow) -> Result<()> {
+    let mut img = format::load(path, "the shadow")?.to_rgba8();
+    round_corners(&mut img, shadow.radius);
+
+    let pad = shadow.padding;
+    let (w, h) = (img.width() + 2 * pad, img.height() + 2 * pad);
+    let [r, g, b, a] = shadow.color;
+    // same color everywhere so blurring only spreads the alpha
+    let mut canvas = RgbaImage::from_pixel(w, h, Rgba([r, g, b, 0]));
+    // drop the shadow a little below the image, like a light from above
+    let drop = (shadow.blur / 2.0) as i64;
+    for (x, y, px) in img.enumerate_pixels() {
+        let (cx, cy) = (x + pad, y as i64 + i64::from(pad) + drop);
+        if let Ok(cy) = u32::try_from(cy)
+            && cy < h
+        {
+            let alpha = u16::from(px[3]) * u16::from(a) / 255;
+            canvas.put_pixel(cx, cy, Rgba([r, g, b, alpha as u8]));
+        }
+    }
+    if shadow.blur > 0.0 {
+        canvas = imageops::blur(&canvas, shadow.blur / 2.0);
+    }
+    imageops::overlay(&mut canvas, &img, i64::from(pad), i64::from(pad));
+
+    format::save_png(path, canvas, "shadowed")
+}
+
+/// Fade the pixels outside a `radius` quarter circle in each corner to transparent
+fn round_corners(img: &mut RgbaImage, radius: u32) {
+    let radius = radius.min(img.width() / 2).min(img.height() / 2);
+    if radius == 0 {
+        return;
+    }
+    let (w, h) = (img.width(), img.height());
+    let r = radius as f32;
+    for dy in 0..radius {
+        for dx in 0..radius {
+            // distance from the corner circle's center, sampled at the pixel center
+            let (fx, fy) = (r - dx as f32 - 0.5, r - dy as f32 - 0.5);
+            let coverage = (r - (fx * fx + fy * fy).sqrt() + 0.5).clamp(0.0, 1.0);
+            if coverage >= 1.0 {
+                continue;
+            }
+            for (x, y) in [
+                (dx, dy),
+                (w - 1 - dx, dy),
+                (dx, h - 1 - dy),
+                (w - 1 - dx, h - 1 - dy),
+            ] {
+                let px = img.get_pixel_mut(x, y);
+                px[3] = (f32::from(px[3]) * coverage).round() as u8;
+            }
+        }
+    }
+}
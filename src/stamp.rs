@@ -0,0 +1,93 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use image::{Rgba, RgbaImage};
+use std::path::Path;
+
+use crate::format;
+
+/// DejaVu Sans Mono Bold, see `assets/DejaVu-LICENSE`
+const FONT: &[u8] = include_bytes!("../assets/DejaVuSansMono-Bold.ttf");
+
+/// Corner the `--stamp` label goes in
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum Position {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+/// Draw `text` as white on a translucent dark box in a corner of the png at
+/// `path`, sized relative to the image height
+pub fn stamp(path: &Path, text: &str, pos: Position) -> Result<()> {
+    let mut img = format::load(path, "stamping")?.to_rgba8();
+
+    let font = FontRef::try_from_slice(FONT).context("loading stamp font")?;
+    let size = (img.height() as f32 / 40.0).max(14.0);
+    let scale = PxScale::from(size);
+    let scaled = font.as_scaled(scale);
+
+    let mut glyphs = Vec::new();
+    let mut x = 0.0;
+    let mut prev = None;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        if let Some(prev) = prev {
+            x += scaled.kern(prev, id);
+        }
+        glyphs.push(id.with_scale_and_position(scale, point(x, scaled.ascent())));
+        x += scaled.h_advance(id);
+        prev = Some(id);
+    }
+    let text_w = x.ceil() as i64;
+    let text_h = scaled.height().ceil() as i64;
+
+    let pad = (size / 4.0).ceil() as i64;
+    let margin = (size / 2.0).ceil() as i64;
+    let (box_w, box_h) = (text_w + 2 * pad, text_h + 2 * pad);
+    let (img_w, img_h) = (i64::from(img.width()), i64::from(img.height()));
+    let left = match pos {
+        Position::TopLeft | Position::BottomLeft => margin,
+        Position::TopRight | Position::BottomRight => img_w - margin - box_w,
+    };
+    let top = match pos {
+        Position::TopLeft | Position::TopRight => margin,
+        Position::BottomLeft | Position::BottomRight => img_h - margin - box_h,
+    };
+
+    for y in top..top + box_h {
+        for x in left..left + box_w {
+            blend(&mut img, x, y, [0, 0, 0], 0.55);
+        }
+    }
+    let (ox, oy) = (left + pad, top + pad);
+    for glyph in glyphs {
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let x = ox + bounds.min.x as i64 + i64::from(gx);
+            let y = oy + bounds.min.y as i64 + i64::from(gy);
+            blend(&mut img, x, y, [255, 255, 255], coverage);
+        });
+    }
+
+    format::save_png(path, img, "stamped")
+}
+
+/// Mix `color` into the pixel at `x`, `y` by `alpha`; off-image points are ignored
+fn blend(img: &mut RgbaImage, x: i64, y: i64, color: [u8; 3], alpha: f32) {
+    let (Ok(x), Ok(y)) = (u32::try_from(x), u32::try_from(y)) else {
+        return;
+    };
+    if x >= img.width() || y >= img.height() {
+        return;
+    }
+    let Rgba(px) = img.get_pixel_mut(x, y);
+    for (channel, c) in px.iter_mut().zip(color) {
+        *channel = (f32::from(*channel) * (1.0 - alpha) + f32::from(c) * alpha).round() as u8;
+    }
+}
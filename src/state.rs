@@ -0,0 +1,45 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{env, fs, path::PathBuf};
+
+use crate::home;
+
+/// `$XDG_STATE_HOME/crabture`, defaulting to `~/.local/state/crabture`
+pub fn dir() -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home().join(".local/state"))
+        .join("crabture")
+}
+
+/// Menu picks from the previous interactive run, by their grimblast names
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LastChoice {
+    pub kind: Option<String>,
+    pub mode: Option<String>,
+}
+
+impl LastChoice {
+    fn path() -> PathBuf {
+        dir().join("last-choice.toml")
+    }
+
+    /// Anything unreadable just means no preference yet
+    pub fn load() -> Self {
+        fs::read_to_string(Self::path())
+            .ok()
+            .and_then(|raw| toml::from_str(&raw).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn store(&self) -> Result<()> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        }
+        fs::write(&path, toml::to_string(self)?)
+            .with_context(|| format!("writing {}", path.display()))
+    }
+}
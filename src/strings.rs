@@ -0,0 +1,73 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::{fs, path::Path};
+
+/// Every user-facing rofi label, so menus can be translated from a TOML file.
+/// Keys left out of the file keep their English default.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Strings {
+    pub when_prompt: String,
+    pub immediate: String,
+    pub delayed: String,
+    pub area_edit: String,
+    pub timer_prompt: String,
+    pub custom_timer: String,
+    pub custom_timer_prompt: String,
+    pub kind_prompt: String,
+    pub capture_screen: String,
+    pub capture_output: String,
+    pub capture_area: String,
+    pub capture_window: String,
+    pub save_prompt: String,
+    pub copy: String,
+    pub save: String,
+    pub copy_save: String,
+    pub edit: String,
+    pub dir_prompt: String,
+    pub countdown_title: String,
+    /// `{secs}` becomes the seconds left
+    pub countdown_body: String,
+    /// Replaces the countdown when Ctrl-C cancels it
+    pub countdown_cancelled: String,
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Self {
+            when_prompt: "Take screenshot".into(),
+            immediate: "Immediate".into(),
+            delayed: "Delayed".into(),
+            area_edit: "Select & Edit".into(),
+            timer_prompt: "Choose timer".into(),
+            custom_timer: "Custom…".into(),
+            custom_timer_prompt: "Seconds".into(),
+            kind_prompt: "Type of screenshot".into(),
+            capture_screen: "Capture Everything".into(),
+            capture_output: "Capture Active Display".into(),
+            capture_area: "Capture Selection".into(),
+            capture_window: "Capture Active Window".into(),
+            save_prompt: "How to save".into(),
+            copy: "Copy".into(),
+            save: "Save".into(),
+            copy_save: "Copy & Save".into(),
+            edit: "Edit".into(),
+            dir_prompt: "Save to".into(),
+            countdown_title: "Taking screenshot".into(),
+            countdown_body: "in {secs} seconds".into(),
+            countdown_cancelled: "Cancelled".into(),
+        }
+    }
+}
+
+impl Strings {
+    pub fn countdown_body(&self, secs: u64) -> String {
+        self.countdown_body.replace("{secs}", &secs.to_string())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let raw =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&raw).with_context(|| format!("parsing strings {}", path.display()))
+    }
+}
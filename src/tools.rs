@@ -0,0 +1,43 @@
+use anyhow::{Result, bail};
+use log::debug;
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+use which::which;
+
+/// The external programs every run may need, resolved once in `main`. Each can
+/// be pointed at a wrapper (or a test fake) with an env var, e.g.
+/// `CRABTURE_GRIMBLAST=~/bin/grimblast-hdr`
+#[derive(Debug, Clone)]
+pub struct Tools {
+    pub grimblast: PathBuf,
+    pub rofi: PathBuf,
+    pub notify_send: PathBuf,
+}
+
+impl Tools {
+    pub fn from_env() -> Self {
+        Self {
+            grimblast: resolve("grimblast", "CRABTURE_GRIMBLAST"),
+            rofi: resolve("rofi", "CRABTURE_ROFI"),
+            notify_send: resolve("notify-send", "CRABTURE_NOTIFY_SEND"),
+        }
+    }
+}
+
+/// Fail early if `tool` can't be run; called where each tool is first needed
+pub fn require(tool: &Path) -> Result<()> {
+    match which(tool) {
+        Ok(path) => debug!("found {} at {}", tool.display(), path.display()),
+        Err(_) => bail!("required tool not found in PATH: {}", tool.display()),
+    }
+    Ok(())
+}
+
+/// The override if set, otherwise the bare name for a PATH lookup
+fn resolve(name: &str, var: &str) -> PathBuf {
+    env::var_os(var)
+        .filter(|p| !p.is_empty())
+        .map_or_else(|| PathBuf::from(name), PathBuf::from)
+}
@@ -0,0 +1,54 @@
+use anyhow::{Context, Result, bail};
+use std::{fs, path::Path, process, time::SystemTime};
+
+use crate::mime_type;
+
+pub const DEFAULT_URL: &str = "https://0x0.st";
+
+/// POST the file as multipart form field `file` and return the URL from the
+/// response body (the convention of 0x0.st and most paste hosts)
+pub fn upload(url: &str, path: &Path) -> Result<String> {
+    let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "screenshot".into());
+    let mime = mime_type(path);
+
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let boundary = format!("crabture-{}-{nanos}", process::id());
+    let mut body = format!(
+        "--{boundary}\r\n\
+         Content-Disposition: form-data; name=\"file\"; filename=\"{file_name}\"\r\n\
+         Content-Type: {mime}\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend_from_slice(&bytes);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+
+    let mut response = ureq::post(url)
+        .header(
+            "Content-Type",
+            format!("multipart/form-data; boundary={boundary}"),
+        )
+        .config()
+        .http_status_as_error(false)
+        .build()
+        .send(&body[..])
+        .with_context(|| format!("uploading to {url}"))?;
+    let status = response.status();
+    let text = response
+        .body_mut()
+        .read_to_string()
+        .context("reading upload response")?;
+    if !status.is_success() {
+        bail!("upload to {url} failed ({status}): {}", text.trim());
+    }
+    let link = text.trim();
+    if link.is_empty() {
+        bail!("upload to {url} returned an empty response");
+    }
+    Ok(link.to_string())
+}
@@ -0,0 +1,353 @@
+//! End-to-end runs of the binary against fake tools, no compositor needed
+
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+/// Scratch HOME with a `bin/` of fake tools; removed on drop
+struct Sandbox {
+    root: PathBuf,
+}
+
+impl Sandbox {
+    fn new(name: &str) -> Self {
+        let root = env::temp_dir().join(format!("crabture-it-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("bin")).unwrap();
+        let sandbox = Self { root };
+        // grimblast writes its output file as the last argument
+        sandbox.script(
+            "grimblast",
+            "for last; do :; done\nprintf 'not really a png' > \"$last\"\n",
+        );
+        sandbox.script("rofi", "exit 1\n");
+        sandbox.script("notify-send", "exit 0\n");
+        sandbox
+    }
+
+    fn script(&self, name: &str, body: &str) -> PathBuf {
+        let path = self.root.join("bin").join(name);
+        fs::write(&path, format!("#!/bin/sh\n{body}")).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn run(&self, args: &[&str], envs: &[(&str, &Path)]) -> Output {
+        let path = format!("{}:/usr/bin:/bin", self.root.join("bin").display());
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_crabture"));
+        cmd.args(args)
+            .current_dir(&self.root)
+            .env_clear()
+            .env("PATH", path)
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", self.root.join("config"))
+            .env("XDG_STATE_HOME", self.root.join("state"));
+        for (key, value) in envs {
+            cmd.env(key, value);
+        }
+        cmd.output().unwrap()
+    }
+
+    /// An `--interactive` run in `save_mode` with no delay, so the capture kind
+    /// is the only menu left; rofi picks `answer` for it
+    fn menu(&self, answer: &str, save_mode: &str, args: &[&str]) -> Output {
+        // read the menu like rofi does, or crabture can hit a closed pipe
+        self.script("rofi", &format!("cat > /dev/null\necho '{answer}'\n"));
+        let mut all = vec![
+            "--interactive",
+            "--save-mode",
+            save_mode,
+            "--delay",
+            "0",
+            "--no-notify",
+        ];
+        all.extend(args);
+        self.run(&all, &[])
+    }
+
+    fn shots(&self) -> PathBuf {
+        self.root.join("shots")
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
+
+fn assert_success(out: &Output) {
+    assert!(
+        out.status.success(),
+        "crabture failed ({}): {}",
+        out.status,
+        String::from_utf8_lossy(&out.stderr)
+    );
+}
+
+#[test]
+fn instant_capture_lands_in_target_dir() {
+    let sandbox = Sandbox::new("instant");
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant",
+            "--no-notify",
+            "--name-template",
+            "shot_{kind}_{seq}",
+            "--print-path",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+    assert_success(&out);
+
+    let expected = shots.join("shot_screen_1.png");
+    assert_eq!(fs::read(&expected).unwrap(), b"not really a png");
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        expected.to_str().unwrap()
+    );
+    // nothing but the capture is left behind
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 1);
+}
+
+#[test]
+fn grimblast_path_can_be_overridden() {
+    let sandbox = Sandbox::new("override");
+    fs::remove_file(sandbox.root.join("bin/grimblast")).unwrap();
+    let fake = sandbox.root.join("wrapper");
+    fs::write(
+        &fake,
+        "#!/bin/sh\nfor last; do :; done\nprintf wrapped > \"$last\"\n",
+    )
+    .unwrap();
+    fs::set_permissions(&fake, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant",
+            "--no-notify",
+            "--name-template",
+            "shot",
+            shots.to_str().unwrap(),
+        ],
+        &[("CRABTURE_GRIMBLAST", &fake)],
+    );
+    assert_success(&out);
+    assert_eq!(fs::read(shots.join("shot.png")).unwrap(), b"wrapped");
+}
+
+#[test]
+fn failed_capture_leaves_no_temp_file() {
+    let sandbox = Sandbox::new("failure");
+    sandbox.script("grimblast", "echo 'grim: boom' >&2\nexit 3\n");
+    let shots = sandbox.shots();
+    let out = sandbox.run(&["--instant", "--no-notify", shots.to_str().unwrap()], &[]);
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("grim: boom"));
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 0);
+}
+
+#[test]
+fn copy_mode_leaves_no_file() {
+    let sandbox = Sandbox::new("copy");
+    let shots = sandbox.shots();
+    let out = sandbox.menu("Capture Everything", "copy", &[shots.to_str().unwrap()]);
+    assert_success(&out);
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 0);
+}
+
+#[test]
+fn empty_capture_is_a_quiet_cancel() {
+    let sandbox = Sandbox::new("empty");
+    sandbox.script("grimblast", "for last; do :; done\n: > \"$last\"\n");
+    let notified = sandbox.root.join("notified");
+    sandbox.script("notify-send", &format!("touch '{}'\n", notified.display()));
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &["--instant-area", "--no-notify", shots.to_str().unwrap()],
+        &[],
+    );
+
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 0);
+    assert!(!notified.exists());
+}
+
+#[test]
+fn empty_capture_notifies_the_cancel() {
+    let sandbox = Sandbox::new("empty-notify");
+    sandbox.script("grimblast", "for last; do :; done\n: > \"$last\"\n");
+    let notified = sandbox.root.join("notified");
+    sandbox.script(
+        "notify-send",
+        &format!("printf '%s\\n' \"$@\" >> '{}'\n", notified.display()),
+    );
+    let shots = sandbox.shots();
+    let out = sandbox.run(&["--instant-area", shots.to_str().unwrap()], &[]);
+
+    assert_eq!(out.status.code(), Some(2));
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 0);
+    let args = fs::read_to_string(&notified).unwrap();
+    assert!(args.lines().any(|l| l == "Selection cancelled"), "{args}");
+}
+
+#[test]
+fn edit_after_pick_after_needs_an_editor() {
+    let sandbox = Sandbox::new("pick-edit");
+    let ran = sandbox.root.join("ran");
+    sandbox.script("grimblast", &format!("touch '{}'\n", ran.display()));
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--area-edit",
+            "--pick-after",
+            "--no-notify",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--editor"));
+    // grimblast must not have opened its editor on the uncropped screen
+    assert!(!ran.exists());
+}
+
+#[test]
+fn share_copy_leaves_the_original_alone() {
+    let sandbox = Sandbox::new("share");
+    let original = sandbox.root.join("wide.png");
+    image::RgbImage::from_pixel(2000, 100, image::Rgb([200, 40, 40]))
+        .save(&original)
+        .unwrap();
+    sandbox.script(
+        "grimblast",
+        &format!(
+            "for last; do :; done\ncat '{}' > \"$last\"\n",
+            original.display()
+        ),
+    );
+    sandbox.script("wl-copy", "cat > /dev/null\n");
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant",
+            "--no-notify",
+            "--no-metadata",
+            "--share-copy",
+            "--name-template",
+            "shot",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+    assert_success(&out);
+
+    assert_eq!(
+        fs::read(shots.join("shot.png")).unwrap(),
+        fs::read(&original).unwrap()
+    );
+    let shared = image::open(shots.join("shared/shot.jpg")).unwrap();
+    assert_eq!((shared.width(), shared.height()), (1280, 64));
+}
+
+#[test]
+fn single_save_dir_is_used_without_asking() {
+    let sandbox = Sandbox::new("one-dir");
+    let work = sandbox.root.join("work");
+    let config = sandbox.root.join("config/crabture");
+    fs::create_dir_all(&config).unwrap();
+    fs::write(
+        config.join("config.toml"),
+        format!("save_dirs = [{:?}]\n", work.display().to_string()),
+    )
+    .unwrap();
+    // a dir menu would get the kind answer back and keep the usual dir
+    let out = sandbox.menu("Capture Everything", "save", &["--name-template", "shot"]);
+    assert_success(&out);
+    assert!(work.join("shot.png").exists());
+}
+
+#[test]
+fn dry_run_creates_nothing() {
+    let sandbox = Sandbox::new("dry-run");
+    let shots = sandbox.shots();
+    let out = sandbox.run(&["--instant", "--dry-run", shots.to_str().unwrap()], &[]);
+    assert_success(&out);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("grimblast"));
+    assert!(!shots.exists());
+}
+
+#[test]
+fn maim_is_asked_for_jpg() {
+    let sandbox = Sandbox::new("maim-jpg");
+    sandbox.script("maim", "exit 0\n");
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant",
+            "--backend",
+            "x11",
+            "--format",
+            "jpg",
+            "--dry-run",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+    assert_success(&out);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("--format jpg"));
+}
+
+#[test]
+fn grim_area_needs_slurp_up_front() {
+    let sandbox = Sandbox::new("grim-slurp");
+    let ran = sandbox.root.join("ran");
+    sandbox.script("grim", &format!("touch '{}'\n", ran.display()));
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant-area",
+            "--backend",
+            "grim",
+            "--no-notify",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("slurp"));
+    assert!(!ran.exists());
+}
+
+#[test]
+fn failed_redaction_saves_nothing() {
+    let sandbox = Sandbox::new("redact");
+    sandbox.script(
+        "hyprctl",
+        "echo '[{\"name\":\"DP-1\",\"width\":10,\"height\":10,\"x\":0,\"y\":0}]'\n",
+    );
+    sandbox.script("slurp", "echo 'failed to connect to display' >&2\nexit 1\n");
+    let shots = sandbox.shots();
+    let out = sandbox.run(
+        &[
+            "--instant",
+            "--redact",
+            "--no-notify",
+            shots.to_str().unwrap(),
+        ],
+        &[],
+    );
+
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("failed to connect"));
+    assert_eq!(fs::read_dir(&shots).unwrap().count(), 0);
+}